@@ -1,6 +1,13 @@
 use std::env;
+use std::fs;
+use std::path::Path;
 
 fn main() {
+    println!("cargo:rerun-if-changed=src/logging.c");
+    cc::Build::new()
+        .file("src/logging.c")
+        .compile("lilv-rs-logging");
+    build_test_bundle();
     if env::var("DOCS_RS") == Ok("1".to_string()) {
         return;
     }
@@ -13,3 +20,27 @@ fn main() {
         .probe("serd-0")
         .expect("serd-0 could not be found with pkg_config.");
 }
+
+// Copy the test bundle to `OUT_DIR` along with its plugin binary, which the
+// tests load with `World::with_test_bundle`.
+fn build_test_bundle() {
+    let source = Path::new("tests/bundles/lilv-test.lv2");
+    println!("cargo:rerun-if-changed={}", source.display());
+    let bundle = Path::new(&env::var("OUT_DIR").unwrap()).join("lilv-test.lv2");
+    fs::create_dir_all(&bundle).unwrap();
+    for file in &["manifest.ttl", "lilv-test.ttl"] {
+        fs::copy(source.join(file), bundle.join(file)).unwrap();
+    }
+    if env::var("CARGO_CFG_TARGET_FAMILY") != Ok("unix".to_string()) {
+        return;
+    }
+    let status = cc::Build::new()
+        .get_compiler()
+        .to_command()
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(bundle.join("lilv-test.so"))
+        .arg(source.join("lilv-test.c"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "the test plugin could not be compiled");
+}
@@ -1,11 +1,12 @@
-use lilv::{node::Node, plugin::Plugin, port::FloatRanges, World};
+use lilv::{
+    node::Node,
+    plugin::Plugin,
+    port::{FloatRanges, PortKind},
+    World,
+};
 
 struct Nodes {
-    control_class: Node,
-    event_class: Node,
     group_pred: Node,
-    label_pred: Node,
-    preset_class: Node,
     designation_pred: Node,
     supports_event_pred: Node,
 }
@@ -31,7 +32,7 @@ fn print_port(p: &Plugin, index: usize, port_ranges: &FloatRanges, nodes: &Nodes
         print!("{}", value.as_uri().unwrap());
     }
 
-    if port.is_a(&nodes.event_class) {
+    if port.kind() == PortKind::Atom {
         let supported = port.value(&nodes.supports_event_pred);
         if supported.count() > 0 {
             println!("\n\t\tSupported events:\n");
@@ -71,7 +72,7 @@ fn print_port(p: &Plugin, index: usize, port_ranges: &FloatRanges, nodes: &Nodes
         println!("\t\tDesignation: {}", designation.as_str().unwrap(),);
     }
 
-    if port.is_a(&nodes.control_class) {
+    if port.kind() == PortKind::Control {
         let (min, max, def) = (port_ranges.min, port_ranges.max, port_ranges.default);
 
         if !min.is_nan() {
@@ -98,7 +99,7 @@ fn print_port(p: &Plugin, index: usize, port_ranges: &FloatRanges, nodes: &Nodes
 }
 
 #[allow(clippy::too_many_lines)]
-fn print_plugin(world: &World, p: &Plugin, nodes: &Nodes) {
+fn print_plugin(p: &Plugin, nodes: &Nodes) {
     println!("{}\n", p.uri().as_uri().unwrap());
     println!("\tName:              {}", p.name().as_str().unwrap());
     println!(
@@ -203,23 +204,14 @@ fn print_plugin(world: &World, p: &Plugin, nodes: &Nodes) {
         println!();
     }
 
-    if let Some(presets) = p.related(Some(&nodes.preset_class)) {
-        if presets.count() != 0 {
-            println!("\tPresets: ");
+    let presets = p.presets();
+    if !presets.is_empty() {
+        println!("\tPresets: ");
 
-            for preset in presets {
-                world.load_resource(&preset).unwrap();
-
-                let titles = world.find_nodes(Some(&preset), &nodes.label_pred, None);
-                if titles.count() > 0 {
-                    if let Some(title) = titles.iter().next() {
-                        println!("\t         {}", title.as_str().unwrap());
-                    } else {
-                        println!("\t         <{}>", preset.as_uri().unwrap());
-                    }
-                } else {
-                    println!("\t         <{}>", preset.as_uri().unwrap());
-                }
+        for preset in presets {
+            match preset.label {
+                Some(label) => println!("\t         {}", label),
+                None => println!("\t         <{}>", preset.uri.as_uri().unwrap()),
             }
         }
     }
@@ -237,16 +229,12 @@ fn main() {
     w.load_all();
 
     let nodes = Nodes {
-        control_class: w.new_uri("http://lv2plug.in/ns/lv2core#ControlPort"),
-        event_class: w.new_uri("http://lv2plug.in/ns/ext/atom#AtomPort"),
         group_pred: w.new_uri("http://lv2plug.in/ns/ext/port-groups#group"),
-        label_pred: w.new_uri("http://www.w3.org/2000/01/rdf-schema#label"),
-        preset_class: w.new_uri("http://lv2plug.in/ns/ext/presets#Preset"),
         designation_pred: w.new_uri("http://lv2plug.in/ns/lv2core#designation"),
         supports_event_pred: w.new_uri("http://lv2plug.in/ns/ext/atom#supportsEvent"),
     };
 
     for p in w.plugins().iter().filter(Plugin::verify) {
-        print_plugin(&w, &p, &nodes);
+        print_plugin(&p, &nodes);
     }
 }
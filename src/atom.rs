@@ -0,0 +1,534 @@
+use crate::urid::UridMapper;
+use lv2_raw::LV2Urid;
+use std::convert::TryInto;
+
+/// The URI for `atom:Sequence`.
+pub const ATOM_SEQUENCE_URI: &str = "http://lv2plug.in/ns/ext/atom#Sequence";
+/// The URI for `atom:Chunk`.
+pub const ATOM_CHUNK_URI: &str = "http://lv2plug.in/ns/ext/atom#Chunk";
+/// The URI for `midi:MidiEvent`.
+pub const MIDI_EVENT_URI: &str = "http://lv2plug.in/ns/ext/midi#MidiEvent";
+
+// The size of an atom header, an event header or an object body header.
+const HEADER_SIZE: usize = 8;
+
+/// The URIDs of the atom types that are used to read and write atoms.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtomUrids {
+    /// `atom:Sequence`
+    pub sequence: LV2Urid,
+    /// `atom:Chunk`
+    pub chunk: LV2Urid,
+    /// `atom:Object`
+    pub object: LV2Urid,
+    /// `atom:Blank`, the deprecated type for objects without an id.
+    pub blank: LV2Urid,
+    /// `atom:Int`
+    pub int: LV2Urid,
+    /// `atom:Long`
+    pub long: LV2Urid,
+    /// `atom:Float`
+    pub float: LV2Urid,
+    /// `atom:Double`
+    pub double: LV2Urid,
+    /// `atom:Bool`
+    pub bool: LV2Urid,
+    /// `atom:URID`
+    pub urid: LV2Urid,
+    /// `atom:String`
+    pub string: LV2Urid,
+    /// `atom:Path`
+    pub path: LV2Urid,
+    /// `atom:URI`
+    pub uri: LV2Urid,
+    /// `midi:MidiEvent`
+    pub midi_event: LV2Urid,
+}
+
+impl AtomUrids {
+    /// Map all the atom types with `mapper`.
+    #[must_use]
+    pub fn new(mapper: &UridMapper) -> AtomUrids {
+        let atom = |name: &str| mapper.map(&format!("http://lv2plug.in/ns/ext/atom#{}", name));
+        AtomUrids {
+            sequence: mapper.map(ATOM_SEQUENCE_URI),
+            chunk: mapper.map(ATOM_CHUNK_URI),
+            object: atom("Object"),
+            blank: atom("Blank"),
+            int: atom("Int"),
+            long: atom("Long"),
+            float: atom("Float"),
+            double: atom("Double"),
+            bool: atom("Bool"),
+            urid: atom("URID"),
+            string: atom("String"),
+            path: atom("Path"),
+            uri: atom("URI"),
+            midi_event: mapper.map(MIDI_EVENT_URI),
+        }
+    }
+}
+
+/// A value that can be written as an atom.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AtomValue<'a> {
+    /// An `atom:Int`.
+    Int(i32),
+    /// An `atom:Long`.
+    Long(i64),
+    /// An `atom:Float`.
+    Float(f32),
+    /// An `atom:Double`.
+    Double(f64),
+    /// An `atom:Bool`.
+    Bool(bool),
+    /// An `atom:URID`.
+    Urid(LV2Urid),
+    /// An `atom:String`.
+    String(&'a str),
+    /// An `atom:Path`.
+    Path(&'a str),
+    /// An `atom:URI`.
+    Uri(&'a str),
+    /// A `midi:MidiEvent`.
+    Midi(&'a [u8]),
+    /// An `atom:Object` with `(key, value)` properties.
+    Object {
+        /// The URID of the object's subject, or 0 for a blank object.
+        id: LV2Urid,
+        /// The URID of the type of the object.
+        otype: LV2Urid,
+        /// The `(key, value)` pairs of the object.
+        properties: &'a [(LV2Urid, AtomValue<'a>)],
+    },
+    /// An atom of any type with its raw body.
+    Raw {
+        /// The URID of the type of the atom.
+        type_urid: LV2Urid,
+        /// The body of the atom.
+        body: &'a [u8],
+    },
+}
+
+impl AtomValue<'_> {
+    /// The URID of the type of the atom.
+    #[must_use]
+    pub fn type_urid(&self, urids: &AtomUrids) -> LV2Urid {
+        match self {
+            AtomValue::Int(_) => urids.int,
+            AtomValue::Long(_) => urids.long,
+            AtomValue::Float(_) => urids.float,
+            AtomValue::Double(_) => urids.double,
+            AtomValue::Bool(_) => urids.bool,
+            AtomValue::Urid(_) => urids.urid,
+            AtomValue::String(_) => urids.string,
+            AtomValue::Path(_) => urids.path,
+            AtomValue::Uri(_) => urids.uri,
+            AtomValue::Midi(_) => urids.midi_event,
+            AtomValue::Object { .. } => urids.object,
+            AtomValue::Raw { type_urid, .. } => *type_urid,
+        }
+    }
+
+    /// The size of the body of the atom in bytes, excluding the atom header.
+    #[must_use]
+    pub fn body_size(&self) -> usize {
+        match self {
+            AtomValue::Int(_) | AtomValue::Float(_) | AtomValue::Bool(_) | AtomValue::Urid(_) => 4,
+            AtomValue::Long(_) | AtomValue::Double(_) => 8,
+            AtomValue::String(s) | AtomValue::Path(s) | AtomValue::Uri(s) => s.len() + 1,
+            AtomValue::Midi(data) | AtomValue::Raw { body: data, .. } => data.len(),
+            AtomValue::Object { properties, .. } => {
+                HEADER_SIZE
+                    + properties
+                        .iter()
+                        .map(|(_, value)| 2 * HEADER_SIZE + pad_size(value.body_size()))
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    fn write_body(&self, urids: &AtomUrids, out: &mut Cursor) {
+        match self {
+            AtomValue::Int(v) => out.put(&v.to_ne_bytes()),
+            AtomValue::Long(v) => out.put(&v.to_ne_bytes()),
+            AtomValue::Float(v) => out.put(&v.to_ne_bytes()),
+            AtomValue::Double(v) => out.put(&v.to_ne_bytes()),
+            AtomValue::Bool(v) => out.put(&i32::from(*v).to_ne_bytes()),
+            AtomValue::Urid(v) => out.put(&v.to_ne_bytes()),
+            AtomValue::String(s) | AtomValue::Path(s) | AtomValue::Uri(s) => {
+                out.put(s.as_bytes());
+                out.put(&[0]);
+            }
+            AtomValue::Midi(data) | AtomValue::Raw { body: data, .. } => out.put(data),
+            AtomValue::Object {
+                id,
+                otype,
+                properties,
+            } => {
+                out.put(&id.to_ne_bytes());
+                out.put(&otype.to_ne_bytes());
+                for (key, value) in properties.iter() {
+                    out.put(&key.to_ne_bytes());
+                    out.put(&0u32.to_ne_bytes());
+                    value.write(urids, out);
+                    out.pad();
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write(&self, urids: &AtomUrids, out: &mut Cursor) {
+        out.put(&(self.body_size() as u32).to_ne_bytes());
+        out.put(&self.type_urid(urids).to_ne_bytes());
+        self.write_body(urids, out);
+    }
+}
+
+/// Writes events into an `atom:Sequence` buffer, such as the buffer of an
+/// input atom port.
+///
+/// Events must be written in order of time. Writing does not allocate, so it
+/// is safe to use in a realtime context.
+pub struct SequenceWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    urids: AtomUrids,
+    last_frame: i64,
+}
+
+impl<'a> SequenceWriter<'a> {
+    /// Start a new empty sequence in `buffer`. Any previous contents are
+    /// discarded.
+    ///
+    /// Returns `None` if `buffer` is not 64-bit aligned or is too small to
+    /// hold an empty sequence.
+    #[must_use]
+    pub fn new(buffer: &'a mut [u8], urids: &AtomUrids) -> Option<SequenceWriter<'a>> {
+        if buffer.as_ptr().align_offset(8) != 0 || buffer.len() < 2 * HEADER_SIZE {
+            return None;
+        }
+        let mut writer = SequenceWriter {
+            buffer,
+            len: 0,
+            urids: *urids,
+            last_frame: 0,
+        };
+        let mut out = writer.cursor();
+        out.put(&(HEADER_SIZE as u32).to_ne_bytes());
+        out.put(&urids.sequence.to_ne_bytes());
+        // A unit of 0 means the event times are in audio frames.
+        out.put(&0u32.to_ne_bytes());
+        out.put(&0u32.to_ne_bytes());
+        writer.len = 2 * HEADER_SIZE;
+        Some(writer)
+    }
+
+    /// The number of bytes of the buffer that are used, including the header.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no events have been written.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 2 * HEADER_SIZE
+    }
+
+    /// The number of bytes that are still available for events.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.len
+    }
+
+    /// Write an event with `value` at `frame`. Returns `false` if there is not
+    /// enough space left or if `frame` is before the previous event.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write(&mut self, frame: i64, value: &AtomValue) -> bool {
+        let size = pad_size(2 * HEADER_SIZE + value.body_size());
+        if size > self.remaining() || frame < self.last_frame {
+            return false;
+        }
+        let urids = self.urids;
+        let mut out = self.cursor();
+        out.put(&frame.to_ne_bytes());
+        value.write(&urids, &mut out);
+        out.pad();
+        self.len += size;
+        self.last_frame = frame;
+        let atom_size = (self.len - HEADER_SIZE) as u32;
+        self.buffer[..4].copy_from_slice(&atom_size.to_ne_bytes());
+        true
+    }
+
+    /// Write a MIDI message at `frame`.
+    pub fn write_midi(&mut self, frame: i64, data: &[u8]) -> bool {
+        self.write(frame, &AtomValue::Midi(data))
+    }
+
+    /// Write an object with `properties` at `frame`.
+    pub fn write_object(
+        &mut self,
+        frame: i64,
+        id: LV2Urid,
+        otype: LV2Urid,
+        properties: &[(LV2Urid, AtomValue)],
+    ) -> bool {
+        self.write(
+            frame,
+            &AtomValue::Object {
+                id,
+                otype,
+                properties,
+            },
+        )
+    }
+
+    fn cursor(&mut self) -> Cursor<'_> {
+        Cursor {
+            buffer: &mut self.buffer[self.len..],
+            position: 0,
+        }
+    }
+}
+
+struct Cursor<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Cursor<'_> {
+    fn put(&mut self, data: &[u8]) {
+        self.buffer[self.position..self.position + data.len()].copy_from_slice(data);
+        self.position += data.len();
+    }
+
+    fn pad(&mut self) {
+        let padded = pad_size(self.position);
+        for b in &mut self.buffer[self.position..padded] {
+            *b = 0;
+        }
+        self.position = padded;
+    }
+}
+
+/// An atom within a buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Atom<'a> {
+    /// The URID of the type of the atom.
+    pub type_urid: LV2Urid,
+    /// The body of the atom, excluding the header.
+    pub body: &'a [u8],
+}
+
+impl<'a> Atom<'a> {
+    /// Parse an atom, including its header, from the start of `data`.
+    #[must_use]
+    pub fn from_bytes(data: &'a [u8]) -> Option<Atom<'a>> {
+        let size = read_u32(data, 0)? as usize;
+        let type_urid = read_u32(data, 4)?;
+        let body = data.get(HEADER_SIZE..HEADER_SIZE + size)?;
+        Some(Atom { type_urid, body })
+    }
+
+    /// Convert the atom to a value. Objects are returned as `AtomValue::Raw`,
+    /// use `Atom::object` to read them instead.
+    #[must_use]
+    pub fn value(&self, urids: &AtomUrids) -> AtomValue<'a> {
+        let t = self.type_urid;
+        let body = self.body;
+        let text = || {
+            let end = body.iter().position(|b| *b == 0).unwrap_or(body.len());
+            std::str::from_utf8(&body[..end]).ok()
+        };
+        let value = if t == urids.int {
+            read_bytes(body).map(|b| AtomValue::Int(i32::from_ne_bytes(b)))
+        } else if t == urids.long {
+            read_bytes(body).map(|b| AtomValue::Long(i64::from_ne_bytes(b)))
+        } else if t == urids.float {
+            read_bytes(body).map(|b| AtomValue::Float(f32::from_ne_bytes(b)))
+        } else if t == urids.double {
+            read_bytes(body).map(|b| AtomValue::Double(f64::from_ne_bytes(b)))
+        } else if t == urids.bool {
+            read_bytes(body).map(|b| AtomValue::Bool(i32::from_ne_bytes(b) != 0))
+        } else if t == urids.urid {
+            read_bytes(body).map(|b| AtomValue::Urid(u32::from_ne_bytes(b)))
+        } else if t == urids.string {
+            text().map(AtomValue::String)
+        } else if t == urids.path {
+            text().map(AtomValue::Path)
+        } else if t == urids.uri {
+            text().map(AtomValue::Uri)
+        } else if t == urids.midi_event {
+            Some(AtomValue::Midi(body))
+        } else {
+            None
+        };
+        value.unwrap_or(AtomValue::Raw { type_urid: t, body })
+    }
+
+    /// Read the atom as an object. Returns `None` if the atom is not an
+    /// `atom:Object` or an `atom:Blank`.
+    #[must_use]
+    pub fn object(&self, urids: &AtomUrids) -> Option<AtomObject<'a>> {
+        if self.type_urid != urids.object && self.type_urid != urids.blank {
+            return None;
+        }
+        Some(AtomObject {
+            id: read_u32(self.body, 0)?,
+            otype: read_u32(self.body, 4)?,
+            properties: self.body.get(HEADER_SIZE..)?,
+        })
+    }
+}
+
+/// An object atom that has been read from a buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtomObject<'a> {
+    /// The URID of the object's subject, or 0 for a blank object.
+    pub id: LV2Urid,
+    /// The URID of the type of the object.
+    pub otype: LV2Urid,
+    properties: &'a [u8],
+}
+
+impl<'a> AtomObject<'a> {
+    /// An iterator over the `(key, value)` pairs of the object.
+    pub fn properties(&self) -> impl 'a + Iterator<Item = (LV2Urid, Atom<'a>)> {
+        let data = self.properties;
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let key = read_u32(data, offset)?;
+            let value = Atom::from_bytes(data.get(offset + HEADER_SIZE..)?)?;
+            offset += pad_size(2 * HEADER_SIZE + value.body.len());
+            Some((key, value))
+        })
+    }
+
+    /// Get the value of the property with `key`.
+    #[must_use]
+    pub fn get(&self, key: LV2Urid) -> Option<Atom<'a>> {
+        self.properties().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+}
+
+/// An event within an `atom:Sequence`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtomEvent<'a> {
+    /// The time of the event in frames, relative to the start of the block.
+    pub frame: i64,
+    /// The body of the event.
+    pub atom: Atom<'a>,
+}
+
+/// An iterator over the events of an `atom:Sequence`, such as the buffer of an
+/// output atom port after `run`.
+#[derive(Clone, Debug)]
+pub struct SequenceIter<'a> {
+    events: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> SequenceIter<'a> {
+    /// Read the sequence in `buffer`. Returns `None` if `buffer` does not
+    /// contain an `atom:Sequence`.
+    #[must_use]
+    pub fn new(buffer: &'a [u8], urids: &AtomUrids) -> Option<SequenceIter<'a>> {
+        let sequence = Atom::from_bytes(buffer)?;
+        if sequence.type_urid != urids.sequence {
+            return None;
+        }
+        Some(SequenceIter {
+            events: sequence.body.get(HEADER_SIZE..)?,
+            offset: 0,
+        })
+    }
+}
+
+impl<'a> Iterator for SequenceIter<'a> {
+    type Item = AtomEvent<'a>;
+
+    fn next(&mut self) -> Option<AtomEvent<'a>> {
+        let data = self.events.get(self.offset..)?;
+        let frame = i64::from_ne_bytes(read_bytes(data)?);
+        let atom = Atom::from_bytes(data.get(HEADER_SIZE..)?)?;
+        self.offset += pad_size(2 * HEADER_SIZE + atom.body.len());
+        Some(AtomEvent { frame, atom })
+    }
+}
+
+/// Round `size` up to the next multiple of 8.
+fn pad_size(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+fn read_bytes<const N: usize>(data: &[u8]) -> Option<[u8; N]> {
+    data.get(..N)?.try_into().ok()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data.get(offset..)?).map(u32::from_ne_bytes)
+}
+
+/// A buffer with the 64-bit alignment that atoms require, for tests that write
+/// sequences.
+#[cfg(test)]
+pub(crate) struct SequenceBuffer(Vec<u64>);
+
+#[cfg(test)]
+impl SequenceBuffer {
+    /// Allocate a zeroed buffer of at least `size` bytes.
+    pub(crate) fn new(size: usize) -> SequenceBuffer {
+        SequenceBuffer(vec![0; size.div_ceil(8)])
+    }
+
+    /// The bytes of the buffer.
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.0.len() * 8;
+        unsafe { std::slice::from_raw_parts_mut(self.0.as_mut_ptr().cast::<u8>(), len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_read_sequence() {
+        let mapper = UridMapper::new();
+        let urids = AtomUrids::new(&mapper);
+        let key = mapper.map("http://example.org/key");
+        let otype = mapper.map("http://example.org/Type");
+        let mut buffer = SequenceBuffer::new(256);
+        let bytes = buffer.bytes_mut();
+
+        let mut writer = SequenceWriter::new(bytes, &urids).unwrap();
+        assert!(writer.is_empty());
+        assert!(writer.write_midi(0, &[0x90, 60, 100]));
+        assert!(writer.write_object(4, 0, otype, &[(key, AtomValue::Float(0.5))]));
+        assert!(!writer.write_midi(2, &[0x80, 60, 0]));
+        assert!(!writer.write(
+            8,
+            &AtomValue::Raw {
+                type_urid: 1,
+                body: &[0; 256]
+            }
+        ));
+
+        let events: Vec<_> = SequenceIter::new(bytes, &urids).unwrap().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].frame, 0);
+        assert_eq!(
+            events[0].atom.value(&urids),
+            AtomValue::Midi(&[0x90, 60, 100])
+        );
+        let object = events[1].atom.object(&urids).unwrap();
+        assert_eq!(object.otype, otype);
+        assert_eq!(
+            object.get(key).map(|v| v.value(&urids)),
+            Some(AtomValue::Float(0.5))
+        );
+    }
+}
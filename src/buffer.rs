@@ -0,0 +1,449 @@
+use crate::atom::{AtomUrids, SequenceIter, SequenceWriter};
+use crate::instance::Instance;
+use crate::plugin::Plugin;
+use crate::port::{Port, PortDirection, PortKind};
+use crate::urid::UridMapper;
+use lv2_raw::LV2AtomSequence;
+use std::ffi::c_void;
+use std::fmt::Debug;
+
+enum PortData {
+    Control(Box<f32>),
+    Audio(Vec<f32>),
+    Cv(Vec<f32>),
+    // Stored as `u64` to satisfy the 64-bit alignment required by atoms.
+    AtomSequence(Vec<u64>),
+    Unsupported,
+}
+
+impl PortData {
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            PortData::Control(value) => (&**value as *const f32).cast(),
+            PortData::Audio(data) | PortData::Cv(data) => data.as_ptr().cast(),
+            PortData::AtomSequence(data) => data.as_ptr().cast(),
+            PortData::Unsupported => std::ptr::null(),
+        }
+    }
+}
+
+/// The storage for a single port.
+pub struct PortBuffer {
+    index: usize,
+    symbol: String,
+    is_input: bool,
+    data: PortData,
+}
+
+/// Owned and correctly typed storage for all the ports of a plugin.
+///
+/// # Example
+/// ```no_run
+/// use lilv::buffer::PortBuffers;
+/// use lilv::feature::FeatureSet;
+/// use lilv::urid::UridMapper;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world
+///     .plugins()
+///     .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+///     .unwrap();
+/// let mapper = UridMapper::new();
+/// let features = FeatureSet::new().with_urid_mapper(&mapper);
+/// let mut instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+/// let mut buffers = PortBuffers::new(&plugin, &mapper, 64);
+/// unsafe { buffers.connect(&mut instance) };
+/// buffers.set_control("gain", -6.0);
+/// let mut instance = unsafe { instance.activate() };
+/// unsafe { instance.run(64) };
+/// ```
+pub struct PortBuffers {
+    buffers: Vec<PortBuffer>,
+    block_size: usize,
+    urids: AtomUrids,
+}
+
+impl PortBuffers {
+    /// The number of bytes allocated for each atom sequence port by
+    /// `PortBuffers::new`.
+    pub const DEFAULT_ATOM_CAPACITY: usize = 8192;
+
+    /// Allocate buffers for all the ports of `plugin`. Audio and CV ports hold
+    /// `block_size` samples and control ports are set to their default value.
+    #[must_use]
+    pub fn new(plugin: &Plugin, mapper: &UridMapper, block_size: usize) -> PortBuffers {
+        PortBuffers::with_atom_capacity(plugin, mapper, block_size, Self::DEFAULT_ATOM_CAPACITY)
+    }
+
+    /// Allocate buffers for all the ports of `plugin` with `atom_capacity` bytes
+    /// for each atom sequence port.
+    #[must_use]
+    pub fn with_atom_capacity(
+        plugin: &Plugin,
+        mapper: &UridMapper,
+        block_size: usize,
+        atom_capacity: usize,
+    ) -> PortBuffers {
+        let atom_words = atom_capacity
+            .max(std::mem::size_of::<LV2AtomSequence>())
+            .div_ceil(8);
+
+        let buffers = plugin
+            .iter_ports()
+            .map(|port: Port| {
+                let data = match port.kind() {
+                    PortKind::Control => {
+                        let default = port
+                            .range()
+                            .default
+                            .and_then(|n| n.as_float())
+                            .unwrap_or(0.0);
+                        PortData::Control(Box::new(default))
+                    }
+                    PortKind::Audio => PortData::Audio(vec![0.0; block_size]),
+                    PortKind::Cv => PortData::Cv(vec![0.0; block_size]),
+                    PortKind::Atom => PortData::AtomSequence(vec![0; atom_words]),
+                    PortKind::Unknown => PortData::Unsupported,
+                };
+                PortBuffer {
+                    index: port.index(),
+                    symbol: port
+                        .symbol()
+                        .and_then(|n| n.as_str().map(str::to_string))
+                        .unwrap_or_default(),
+                    is_input: port.direction() == PortDirection::Input,
+                    data,
+                }
+            })
+            .collect();
+
+        let mut buffers = PortBuffers {
+            buffers,
+            block_size,
+            urids: AtomUrids::new(mapper),
+        };
+        buffers.reset_atom_sequences();
+        buffers
+    }
+
+    /// The number of samples in each audio and CV buffer.
+    #[must_use]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The number of port buffers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Returns `true` if the plugin has no ports.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// Get the buffer for the port at `index`.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&PortBuffer> {
+        self.buffers.iter().find(|b| b.index == index)
+    }
+
+    /// Get the mutable buffer for the port at `index`.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut PortBuffer> {
+        self.buffers.iter_mut().find(|b| b.index == index)
+    }
+
+    /// Get the buffer for the port with `symbol`.
+    #[must_use]
+    pub fn by_symbol(&self, symbol: &str) -> Option<&PortBuffer> {
+        self.buffers.iter().find(|b| b.symbol == symbol)
+    }
+
+    /// Get the mutable buffer for the port with `symbol`.
+    pub fn by_symbol_mut(&mut self, symbol: &str) -> Option<&mut PortBuffer> {
+        self.buffers.iter_mut().find(|b| b.symbol == symbol)
+    }
+
+    /// An iterator over all the port buffers.
+    pub fn iter(&self) -> impl '_ + Iterator<Item = &PortBuffer> {
+        self.buffers.iter()
+    }
+
+    /// A mutable iterator over all the port buffers.
+    pub fn iter_mut(&mut self) -> impl '_ + Iterator<Item = &mut PortBuffer> {
+        self.buffers.iter_mut()
+    }
+
+    /// Get the value of the control port with `symbol`.
+    #[must_use]
+    pub fn control(&self, symbol: &str) -> Option<f32> {
+        self.by_symbol(symbol)?.control()
+    }
+
+    /// Set the value of the control port with `symbol`. Returns `false` if
+    /// there is no control port with `symbol`.
+    pub fn set_control(&mut self, symbol: &str, value: f32) -> bool {
+        match self.by_symbol_mut(symbol).and_then(PortBuffer::control_mut) {
+            Some(control) => {
+                *control = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The URIDs used for the atom sequence ports.
+    #[must_use]
+    pub fn atom_urids(&self) -> &AtomUrids {
+        &self.urids
+    }
+
+    /// Start writing a new sequence of events into the atom port with
+    /// `symbol`. Returns `None` if there is no atom port with `symbol`.
+    pub fn sequence_writer(&mut self, symbol: &str) -> Option<SequenceWriter<'_>> {
+        let urids = self.urids;
+        let buffer = self.by_symbol_mut(symbol)?.atom_bytes_mut()?;
+        SequenceWriter::new(buffer, &urids)
+    }
+
+    /// Read the events of the atom port with `symbol`. Returns `None` if there
+    /// is no atom port with `symbol` or if it does not contain a sequence.
+    #[must_use]
+    pub fn sequence(&self, symbol: &str) -> Option<SequenceIter<'_>> {
+        SequenceIter::new(self.by_symbol(symbol)?.atom_bytes()?, &self.urids)
+    }
+
+    /// Prepare the atom sequence ports for the next call to `run`. Input
+    /// sequences are cleared and output sequences are marked as empty chunks
+    /// that span the whole buffer.
+    pub fn reset_atom_sequences(&mut self) {
+        self.reset_atoms(true, true);
+    }
+
+    /// Clear the input atom sequences.
+    pub fn reset_atom_inputs(&mut self) {
+        self.reset_atoms(true, false);
+    }
+
+    /// Mark the output atom sequences as empty chunks that span the whole
+    /// buffer so that the plugin can write into them.
+    pub fn reset_atom_outputs(&mut self) {
+        self.reset_atoms(false, true);
+    }
+
+    fn reset_atoms(&mut self, inputs: bool, outputs: bool) {
+        let (sequence_urid, chunk_urid) = (self.urids.sequence, self.urids.chunk);
+        for buffer in &mut self.buffers {
+            let is_input = buffer.is_input;
+            if (is_input && !inputs) || (!is_input && !outputs) {
+                continue;
+            }
+            if let PortData::AtomSequence(data) = &mut buffer.data {
+                let capacity = data.len() * 8;
+                let sequence = unsafe { &mut *data.as_mut_ptr().cast::<LV2AtomSequence>() };
+                sequence.body.unit = 0;
+                sequence.body.pad = 0;
+                #[allow(clippy::cast_possible_truncation)]
+                if is_input {
+                    sequence.atom.mytype = sequence_urid;
+                    sequence.atom.size = std::mem::size_of_val(&sequence.body) as u32;
+                } else {
+                    sequence.atom.mytype = chunk_urid;
+                    sequence.atom.size = (capacity - std::mem::size_of_val(&sequence.atom)) as u32;
+                }
+            }
+        }
+    }
+
+    /// Connect all the buffers to `instance`. Ports with unsupported types are
+    /// connected to null.
+    ///
+    /// # Safety
+    /// Connecting a port calls a plugin's code, which itself may be unsafe.
+    /// The buffers must not be dropped while `instance` may still run.
+    pub unsafe fn connect(&mut self, instance: &mut Instance) {
+        self.reset_atom_sequences();
+        for buffer in &mut self.buffers {
+            let index = buffer.index;
+            match &mut buffer.data {
+                PortData::Control(value) => instance.connect_port_mut::<f32>(index, &mut **value),
+                PortData::Audio(data) | PortData::Cv(data) => {
+                    instance.connect_port_mut(index, data.as_mut_ptr());
+                }
+                PortData::AtomSequence(data) => instance.connect_port_mut(index, data.as_mut_ptr()),
+                PortData::Unsupported => {
+                    instance.connect_port_mut::<f32>(index, std::ptr::null_mut());
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if every port of `instance` is connected to the data of
+    /// these buffers. This fails if a buffer was replaced after `connect`.
+    pub(crate) fn is_connected_to(&self, instance: &Instance) -> bool {
+        self.buffers.len() == instance.ports.len()
+            && self
+                .buffers
+                .iter()
+                .all(|b| Self::is_port_connected_to(b, instance))
+    }
+
+    /// The symbols of the buffers that `instance` is not connected to.
+    pub(crate) fn ports_not_connected_to(&self, instance: &Instance) -> Vec<String> {
+        self.buffers
+            .iter()
+            .filter(|b| !Self::is_port_connected_to(b, instance))
+            .map(|b| b.symbol.clone())
+            .collect()
+    }
+
+    fn is_port_connected_to(buffer: &PortBuffer, instance: &Instance) -> bool {
+        instance.connected_data(buffer.index) == Some(buffer.data.as_ptr())
+    }
+}
+
+impl Debug for PortBuffers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PortBuffers")
+            .field("block_size", &self.block_size)
+            .field("buffers", &self.buffers)
+            .finish()
+    }
+}
+
+impl PortBuffer {
+    /// The index of the port.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The symbol of the port.
+    #[must_use]
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Returns `true` if the port is an input.
+    #[must_use]
+    pub fn is_input(&self) -> bool {
+        self.is_input
+    }
+
+    /// Returns `true` if the port is an output.
+    #[must_use]
+    pub fn is_output(&self) -> bool {
+        !self.is_input
+    }
+
+    /// The kind of data stored in the buffer. Ports of an unknown kind are
+    /// connected to a null pointer, which is only valid if the port is
+    /// `lv2:connectionOptional`.
+    #[must_use]
+    pub fn kind(&self) -> PortKind {
+        match self.data {
+            PortData::Control(_) => PortKind::Control,
+            PortData::Audio(_) => PortKind::Audio,
+            PortData::Cv(_) => PortKind::Cv,
+            PortData::AtomSequence(_) => PortKind::Atom,
+            PortData::Unsupported => PortKind::Unknown,
+        }
+    }
+
+    /// The value of a control port.
+    #[must_use]
+    pub fn control(&self) -> Option<f32> {
+        match &self.data {
+            PortData::Control(value) => Some(**value),
+            _ => None,
+        }
+    }
+
+    /// The mutable value of a control port.
+    pub fn control_mut(&mut self) -> Option<&mut f32> {
+        match &mut self.data {
+            PortData::Control(value) => Some(&mut **value),
+            _ => None,
+        }
+    }
+
+    /// The samples of an audio or CV port.
+    #[must_use]
+    pub fn samples(&self) -> Option<&[f32]> {
+        match &self.data {
+            PortData::Audio(data) | PortData::Cv(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The mutable samples of an audio or CV port.
+    pub fn samples_mut(&mut self) -> Option<&mut [f32]> {
+        match &mut self.data {
+            PortData::Audio(data) | PortData::Cv(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The raw bytes of an atom sequence port, including the atom header. This
+    /// spans the whole capacity of the buffer.
+    #[must_use]
+    pub fn atom_bytes(&self) -> Option<&[u8]> {
+        match &self.data {
+            PortData::AtomSequence(data) => {
+                Some(unsafe { std::slice::from_raw_parts(data.as_ptr().cast(), data.len() * 8) })
+            }
+            _ => None,
+        }
+    }
+
+    /// The mutable raw bytes of an atom sequence port, including the atom
+    /// header. This spans the whole capacity of the buffer.
+    pub fn atom_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.data {
+            PortData::AtomSequence(data) => Some(unsafe {
+                std::slice::from_raw_parts_mut(data.as_mut_ptr().cast(), data.len() * 8)
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for PortBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PortBuffer")
+            .field("index", &self.index)
+            .field("symbol", &self.symbol)
+            .field("is_input", &self.is_input)
+            .field("kind", &self.kind())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_amp_buffers() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let mut buffers = PortBuffers::new(&plugin, &UridMapper::new(), 32);
+        assert_eq!(buffers.len(), 3);
+        assert_eq!(buffers.by_symbol("gain").unwrap().kind(), PortKind::Control);
+        assert_eq!(buffers.control("gain"), Some(0.0));
+        assert!(buffers.set_control("gain", -3.0));
+        assert_eq!(buffers.control("gain"), Some(-3.0));
+        assert!(!buffers.set_control("in", 1.0));
+        assert_eq!(
+            buffers.by_symbol("in").unwrap().samples().unwrap().len(),
+            32
+        );
+        assert!(buffers.by_symbol("out").unwrap().is_output());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::error::Error;
+use crate::info::PluginInfo;
+use crate::node::Node;
+use crate::plugin::Plugin;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// A cache of the plugins in a set of bundles that is stored on disk.
+///
+/// Loading all bundles with `World::load_all` parses every Turtle file, which
+/// is slow on systems with many plugins. A catalogue stores the descriptions
+/// of the plugins along with the modification times of the files of each
+/// bundle. On the next start, only the bundles that changed are loaded.
+///
+/// Plugins from bundles that did not change are not loaded into the world.
+/// Use `Catalogue::load_plugin` to load the bundle of a plugin before
+/// instantiating it.
+///
+/// # Example
+/// ```no_run
+/// use lilv::catalogue::Catalogue;
+/// use std::path::{Path, PathBuf};
+///
+/// let cache = Path::new("/tmp/lv2-catalogue.json");
+/// let world = lilv::World::new();
+/// let mut catalogue = Catalogue::load(cache).unwrap_or_default();
+/// unsafe { catalogue.refresh(&world, &[PathBuf::from("/usr/lib/lv2")]) };
+/// catalogue.save(cache).unwrap();
+/// for plugin in catalogue.plugins() {
+///     println!("{}", plugin.name);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Catalogue {
+    bundles: Vec<CatalogueBundle>,
+}
+
+/// A bundle within a `Catalogue`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CatalogueBundle {
+    /// The URI of the bundle, with a trailing slash.
+    pub uri: String,
+    /// The path of the bundle directory.
+    pub path: PathBuf,
+    /// The files of the bundle that were read to describe its plugins.
+    pub files: Vec<FileStamp>,
+    /// The plugins within the bundle.
+    pub plugins: Vec<PluginInfo>,
+}
+
+/// A file and its modification time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The modification time of the file since the Unix epoch, or `None` if
+    /// the file did not exist.
+    pub modified: Option<Duration>,
+}
+
+impl FileStamp {
+    /// Get the current modification time of the file at `path`.
+    #[must_use]
+    pub fn new(path: PathBuf) -> FileStamp {
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+        FileStamp { path, modified }
+    }
+
+    /// Returns `true` if the file was modified, created or removed since the
+    /// stamp was taken.
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        FileStamp::new(self.path.clone()).modified != self.modified
+    }
+}
+
+impl CatalogueBundle {
+    /// Returns `true` if any of the files of the bundle have changed.
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.files.iter().any(FileStamp::is_stale)
+    }
+}
+
+impl Catalogue {
+    /// Create an empty catalogue.
+    #[must_use]
+    pub fn new() -> Catalogue {
+        Catalogue::default()
+    }
+
+    /// Create a catalogue of all the plugins that are loaded in `world`.
+    #[must_use]
+    pub fn from_world(world: &World) -> Catalogue {
+        let mut bundles: Vec<CatalogueBundle> = Vec::new();
+        for plugin in world.plugins().iter() {
+            let uri = match plugin.bundle_uri().as_uri() {
+                Some(uri) => uri.to_string(),
+                None => continue,
+            };
+            let index = match bundles.iter().position(|b| b.uri == uri) {
+                Some(index) => index,
+                None => match CatalogueBundle::new(uri, &plugin.bundle_uri()) {
+                    Some(bundle) => {
+                        bundles.push(bundle);
+                        bundles.len() - 1
+                    }
+                    None => continue,
+                },
+            };
+            bundles[index].add_plugin(&plugin);
+        }
+        Catalogue { bundles }
+    }
+
+    /// Read a catalogue that was written with `save`.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the file could not be read or is not a valid
+    /// catalogue.
+    pub fn load(path: &Path) -> Result<Catalogue, Error> {
+        let data = std::fs::read(path).map_err(|e| io_error(path, e))?;
+        serde_json::from_slice(&data).map_err(|e| io_error(path, e))
+    }
+
+    /// Write the catalogue to `path`.
+    ///
+    /// # Errors
+    /// Returns `Error::Io` if the file could not be written.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let data = serde_json::to_vec(self).map_err(|e| io_error(path, e))?;
+        std::fs::write(path, data).map_err(|e| io_error(path, e))
+    }
+
+    /// The bundles within the catalogue.
+    #[must_use]
+    pub fn bundles(&self) -> &[CatalogueBundle] {
+        &self.bundles
+    }
+
+    /// Iterate over the descriptions of all the plugins in the catalogue.
+    pub fn plugins(&self) -> impl '_ + Iterator<Item = &PluginInfo> {
+        self.bundles.iter().flat_map(|b| b.plugins.iter())
+    }
+
+    /// Get the description of the plugin with `uri`.
+    #[must_use]
+    pub fn plugin(&self, uri: &str) -> Option<&PluginInfo> {
+        self.plugins().find(|p| p.uri == uri)
+    }
+
+    /// Bring the catalogue up to date with the bundles in `lv2_path`.
+    ///
+    /// Bundles that changed and bundles within `lv2_path` that are not in the
+    /// catalogue are loaded into `world` and described again. Bundles that
+    /// changed are unloaded from `world` first. Bundles that no longer exist
+    /// are removed. Returns the URIs of the bundles that were loaded.
+    ///
+    /// # Safety
+    /// Plugins from bundles that changed must not be in use, see
+    /// `World::unload_bundle`.
+    pub unsafe fn refresh(&mut self, world: &World, lv2_path: &[PathBuf]) -> Vec<String> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        self.bundles.retain(|bundle| {
+            if bundle.is_stale() {
+                if let Ok(uri) = world.try_new_uri(&bundle.uri) {
+                    let _ = world.unload_bundle(&uri);
+                }
+                dirs.push(bundle.path.clone());
+                false
+            } else {
+                true
+            }
+        });
+        for dir in lv2_path {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                let path = path.canonicalize().unwrap_or(path);
+                let is_new = path.join("manifest.ttl").is_file()
+                    && !dirs.contains(&path)
+                    && !self.bundles.iter().any(|b| b.path == path);
+                if is_new {
+                    dirs.push(path);
+                }
+            }
+        }
+
+        let mut bundle_uris = Vec::new();
+        for dir in dirs {
+            if let Ok(uri) = world.load_bundle_path(&dir) {
+                bundle_uris.push(uri);
+            }
+        }
+        if !bundle_uris.is_empty() {
+            world.load_specifications();
+            world.load_plugin_classes();
+        }
+
+        let mut loaded = Vec::new();
+        for uri in bundle_uris {
+            let uri_str = match uri.as_uri() {
+                Some(uri) => uri.to_string(),
+                None => continue,
+            };
+            if let Some(mut bundle) = CatalogueBundle::new(uri_str.clone(), &uri) {
+                for plugin in world.plugins().iter() {
+                    if plugin.bundle_uri() == uri {
+                        bundle.add_plugin(&plugin);
+                    }
+                }
+                self.bundles.push(bundle);
+            }
+            loaded.push(uri_str);
+        }
+        loaded
+    }
+
+    /// Get the plugin with `uri` from `world`, loading its bundle first if it
+    /// is in the catalogue but not in the world.
+    #[must_use]
+    pub fn load_plugin(&self, world: &World, uri: &str) -> Option<Plugin> {
+        let uri_node = world.try_new_uri(uri).ok()?;
+        if let Some(plugin) = world.plugins().plugin(&uri_node) {
+            return Some(plugin);
+        }
+        let bundle = self
+            .bundles
+            .iter()
+            .find(|b| b.plugins.iter().any(|p| p.uri == uri))?;
+        world.load_bundle(&world.try_new_uri(&bundle.uri).ok()?);
+        world.plugins().plugin(&uri_node)
+    }
+}
+
+impl CatalogueBundle {
+    fn new(uri: String, uri_node: &Node) -> Option<CatalogueBundle> {
+        let path = PathBuf::from(uri_node.path()?.1);
+        // The directory changes when files are added or removed.
+        let files = vec![
+            FileStamp::new(path.clone()),
+            FileStamp::new(path.join("manifest.ttl")),
+        ];
+        Some(CatalogueBundle {
+            uri,
+            path,
+            files,
+            plugins: Vec::new(),
+        })
+    }
+
+    fn add_plugin(&mut self, plugin: &Plugin) {
+        let data_uris = plugin.data_uris();
+        let files = data_uris
+            .iter()
+            .chain(plugin.library_uri())
+            .filter_map(|n| n.path().map(|(_, path)| PathBuf::from(path)));
+        for file in files {
+            if !self.files.iter().any(|f| f.path == file) {
+                self.files.push(FileStamp::new(file));
+            }
+        }
+        self.plugins.push(plugin.info());
+    }
+}
+
+fn io_error(path: &Path, err: impl std::fmt::Display) -> Error {
+    Error::Io(format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let world = World::with_load_all();
+        let catalogue = Catalogue::from_world(&world);
+        assert!(catalogue
+            .plugin("http://lv2plug.in/plugins/eg-amp")
+            .is_some());
+        assert!(catalogue.bundles().iter().all(|b| !b.is_stale()));
+
+        let path = std::env::temp_dir().join(format!(
+            "lilv-test-catalogue-{}-save-and-load.json",
+            std::process::id()
+        ));
+        catalogue.save(&path).unwrap();
+        let loaded = Catalogue::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, catalogue);
+
+        let empty = World::new();
+        let plugin = loaded
+            .load_plugin(&empty, "http://lv2plug.in/plugins/eg-amp")
+            .unwrap();
+        assert_eq!(plugin.ports_count(), 3);
+    }
+
+    fn copy_bundle(world: &World, uri: &str, dir: &Path) {
+        let plugin = world.plugins().plugin(&world.new_uri(uri)).unwrap();
+        let from = PathBuf::from(plugin.bundle_uri().path().unwrap().1);
+        let to = dir.join(from.file_name().unwrap());
+        std::fs::create_dir_all(&to).unwrap();
+        for entry in std::fs::read_dir(&from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_file() {
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_refresh_changed_bundle() {
+        let dir = std::env::temp_dir().join(format!(
+            "lilv-test-catalogue-{}-refresh",
+            std::process::id()
+        ));
+        let installed = World::with_load_all();
+        copy_bundle(&installed, "http://lv2plug.in/plugins/eg-amp", &dir);
+        copy_bundle(&installed, "http://lv2plug.in/plugins/eg-fifths", &dir);
+        drop(installed);
+
+        let world = World::new();
+        let lv2_path = vec![dir.clone()];
+        let mut catalogue = Catalogue::new();
+        assert_eq!(unsafe { catalogue.refresh(&world, &lv2_path) }.len(), 2);
+        assert!(unsafe { catalogue.refresh(&world, &lv2_path) }.is_empty());
+
+        let amp = catalogue
+            .bundles()
+            .iter()
+            .find(|b| {
+                b.plugins
+                    .iter()
+                    .any(|p| p.uri == "http://lv2plug.in/plugins/eg-amp")
+            })
+            .unwrap()
+            .clone();
+        let later = std::time::SystemTime::now() + Duration::from_secs(60);
+        std::fs::File::options()
+            .append(true)
+            .open(amp.path.join("manifest.ttl"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            unsafe { catalogue.refresh(&world, &lv2_path) },
+            vec![amp.uri]
+        );
+        assert_eq!(catalogue.bundles().len(), 2);
+        assert!(catalogue
+            .plugin("http://lv2plug.in/plugins/eg-amp")
+            .is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
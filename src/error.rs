@@ -0,0 +1,101 @@
+use crate::options::OptionsStatus;
+use std::fmt::Display;
+
+/// An error that occurred while using Lilv.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The string could not be passed to Lilv because it contains a null
+    /// byte.
+    InvalidString(String),
+    /// The URI is not valid.
+    InvalidUri(String),
+    /// The plugin requires features that were not provided.
+    MissingFeatures {
+        /// The URI of the plugin.
+        plugin: String,
+        /// The URIs of the required features that were not provided.
+        features: Vec<String>,
+    },
+    /// The plugin failed to instantiate.
+    InstantiationFailed(String),
+    /// The bundle does not exist or is not loaded.
+    BundleNotFound(String),
+    /// The data for a resource could not be parsed.
+    ParseFailure(String),
+    /// A file could not be read or written.
+    Io(String),
+    /// The state could not be saved to the given directory.
+    SaveFailed(String),
+    /// The plugin does not have a port with the given symbol.
+    UnknownPort(String),
+    /// The number of channels does not match the number of ports.
+    ChannelMismatch {
+        /// The number of ports.
+        expected: usize,
+        /// The number of channels that were provided.
+        actual: usize,
+    },
+    /// The ports can not be connected.
+    InvalidConnection(String),
+    /// The connection would create a cycle in the graph.
+    GraphCycle,
+    /// The ports with the given symbols must be connected before the instance
+    /// can run.
+    UnconnectedPorts(Vec<String>),
+    /// More frames were requested than the buffers can hold.
+    BlockTooLarge {
+        /// The number of frames that the buffers can hold.
+        block_size: usize,
+        /// The number of frames that were requested.
+        frames: usize,
+    },
+    /// The plugin reported errors while getting or setting options.
+    Options(OptionsStatus),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidString(s) => write!(f, "string contains a null byte: {:?}", s),
+            Error::InvalidUri(uri) => write!(f, "invalid uri: {}", uri),
+            Error::MissingFeatures { plugin, features } => write!(
+                f,
+                "plugin {} requires unsupported features: {}",
+                plugin,
+                features.join(", ")
+            ),
+            Error::InstantiationFailed(plugin) => {
+                write!(f, "failed to instantiate plugin {}", plugin)
+            }
+            Error::BundleNotFound(bundle) => write!(f, "bundle not found: {}", bundle),
+            Error::ParseFailure(resource) => write!(f, "failed to parse {}", resource),
+            Error::Io(err) => write!(f, "i/o error: {}", err),
+            Error::SaveFailed(dir) => write!(f, "failed to save state to {}", dir),
+            Error::UnknownPort(symbol) => write!(f, "no port with symbol {}", symbol),
+            Error::ChannelMismatch { expected, actual } => {
+                write!(f, "expected {} channels but got {}", expected, actual)
+            }
+            Error::InvalidConnection(connection) => {
+                write!(f, "invalid connection: {}", connection)
+            }
+            Error::GraphCycle => write!(f, "the connection would create a cycle"),
+            Error::UnconnectedPorts(symbols) => {
+                write!(f, "ports are not connected: {}", symbols.join(", "))
+            }
+            Error::BlockTooLarge { block_size, frames } => write!(
+                f,
+                "{} frames do not fit in blocks of {} frames",
+                frames, block_size
+            ),
+            Error::Options(status) => write!(f, "options were rejected: {}", status),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Convert `s` to a `CString` or return `Error::InvalidString`.
+pub(crate) fn c_string(s: &str) -> Result<std::ffi::CString, Error> {
+    std::ffi::CString::new(s).map_err(|_| Error::InvalidString(s.to_string()))
+}
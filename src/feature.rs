@@ -0,0 +1,273 @@
+use crate::error::{c_string, Error};
+use crate::logging::Logger;
+use crate::node::Node;
+use crate::options::{Options, BOUNDED_BLOCK_LENGTH_URI, OPTIONS_URI};
+use crate::plugin::Plugin;
+use crate::urid::UridMapper;
+use crate::worker::Worker;
+use lv2_raw::LV2Feature;
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::fmt::Debug;
+use std::os::raw::c_void;
+use std::sync::Arc;
+
+/// A set of features that are provided to plugin instances.
+///
+/// The feature set owns the data for its features. Cloning a `FeatureSet` is
+/// cheap and all clones share the same feature data.
+///
+/// # Example
+/// ```
+/// use lilv::feature::FeatureSet;
+/// use lilv::urid::UridMapper;
+///
+/// let features = FeatureSet::new()
+///     .with_urid_mapper(&UridMapper::new())
+///     .with_flag("http://lv2plug.in/ns/lv2core#isLive");
+/// assert!(features.contains("http://lv2plug.in/ns/ext/urid#map"));
+/// ```
+#[derive(Clone, Default)]
+pub struct FeatureSet {
+    features: Vec<Arc<OwnedFeature>>,
+}
+
+struct OwnedFeature {
+    uri: CString,
+    feature: LV2Feature,
+    // Keeps the data that `feature` points to alive.
+    _owner: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+// The feature data is kept alive by `_owner` and must be safe to share between
+// threads, as required by `FeatureSet::with_raw`.
+unsafe impl Send for OwnedFeature {}
+unsafe impl Sync for OwnedFeature {}
+
+impl FeatureSet {
+    /// Create a new feature set with no features.
+    #[must_use]
+    pub fn new() -> FeatureSet {
+        FeatureSet::default()
+    }
+
+    /// Add the `urid:map` and `urid:unmap` features from `mapper`.
+    #[must_use]
+    pub fn with_urid_mapper(self, mapper: &UridMapper) -> FeatureSet {
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(mapper.clone());
+        let mut features = self;
+        for feature in [mapper.map_feature(), mapper.unmap_feature()] {
+            let uri = unsafe { CStr::from_ptr(feature.uri) }.to_owned();
+            features.insert(uri, feature.data, Some(owner.clone()));
+        }
+        features
+    }
+
+    /// Add the `log:log` feature from `logger`.
+    #[must_use]
+    pub fn with_logger(self, logger: &Logger) -> FeatureSet {
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(logger.clone());
+        let feature = logger.feature();
+        let uri = unsafe { CStr::from_ptr(feature.uri) }.to_owned();
+        let mut features = self;
+        features.insert(uri, feature.data, Some(owner));
+        features
+    }
+
+    /// Add the `opts:options` feature with `options`. If `options` has both a
+    /// minimum and maximum block length, then the `bufsz:boundedBlockLength`
+    /// feature is added as well.
+    #[must_use]
+    pub fn with_options(self, options: &Options) -> FeatureSet {
+        let data = options.feature_data();
+        let ptr = data.as_ptr();
+        let mut features = self;
+        features.insert(CString::new(OPTIONS_URI).unwrap(), ptr, Some(data));
+        if options.is_bounded() {
+            features = features.with_flag(BOUNDED_BLOCK_LENGTH_URI);
+        }
+        features
+    }
+
+    /// Add the `work:schedule` feature from `worker`.
+    #[must_use]
+    pub fn with_worker(self, worker: &Worker) -> FeatureSet {
+        let feature = worker.feature();
+        let uri = unsafe { CStr::from_ptr(feature.uri) }.to_owned();
+        let mut features = self;
+        features.insert(uri, feature.data, Some(worker.feature_owner()));
+        features
+    }
+
+    /// Add a feature that has no data, for example `lv2:isLive`.
+    ///
+    /// # Panics
+    /// Panics if `uri` contains a null byte. Use `try_with_flag` to handle
+    /// the error instead.
+    #[must_use]
+    pub fn with_flag(self, uri: &str) -> FeatureSet {
+        self.try_with_flag(uri).unwrap()
+    }
+
+    /// Add a feature that has no data, for example `lv2:isLive`.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidString` if `uri` contains a null byte.
+    pub fn try_with_flag(self, uri: &str) -> Result<FeatureSet, Error> {
+        let mut features = self;
+        features.insert(c_string(uri)?, std::ptr::null_mut(), None);
+        Ok(features)
+    }
+
+    /// Add a feature with arbitrary data. If a feature with `uri` already
+    /// exists, then it is replaced.
+    ///
+    /// # Safety
+    /// `data` must point to data that is valid for the feature `uri` and it
+    /// must remain valid for as long as `owner` is alive. If `owner` is `None`,
+    /// then `data` must be valid for the rest of the program. The data may be
+    /// accessed from any thread.
+    ///
+    /// # Panics
+    /// Panics if `uri` contains a null byte. Use `try_with_raw` to handle the
+    /// error instead.
+    #[must_use]
+    pub unsafe fn with_raw(
+        self,
+        uri: &str,
+        data: *mut c_void,
+        owner: Option<Arc<dyn Any + Send + Sync>>,
+    ) -> FeatureSet {
+        self.try_with_raw(uri, data, owner).unwrap()
+    }
+
+    /// Add a feature with arbitrary data. If a feature with `uri` already
+    /// exists, then it is replaced.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidString` if `uri` contains a null byte.
+    ///
+    /// # Safety
+    /// The same requirements as for `with_raw` apply.
+    pub unsafe fn try_with_raw(
+        self,
+        uri: &str,
+        data: *mut c_void,
+        owner: Option<Arc<dyn Any + Send + Sync>>,
+    ) -> Result<FeatureSet, Error> {
+        let mut features = self;
+        features.insert(c_string(uri)?, data, owner);
+        Ok(features)
+    }
+
+    /// Returns `true` if the set contains a feature with `uri`.
+    #[must_use]
+    pub fn contains(&self, uri: &str) -> bool {
+        self.uris().any(|u| u == uri)
+    }
+
+    /// An iterator over the URIs of all the features.
+    pub fn uris(&self) -> impl '_ + Iterator<Item = &str> {
+        self.features.iter().filter_map(|f| f.uri.to_str().ok())
+    }
+
+    /// An iterator over all the features. This can be passed directly to
+    /// `Plugin::instantiate`.
+    pub fn iter(&self) -> impl '_ + Iterator<Item = &LV2Feature> {
+        self.features.iter().map(|f| &f.feature)
+    }
+
+    /// The number of features in the set.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Returns `true` if there are no features in the set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// The URIs of the features that `plugin` requires but are not in the set.
+    /// The plugin can not be instantiated unless this is empty.
+    #[must_use]
+    pub fn missing_required_features(&self, plugin: &Plugin) -> Vec<String> {
+        self.missing(plugin.required_features().iter())
+    }
+
+    /// The URIs of the features that `plugin` may optionally use but are not
+    /// in the set.
+    #[must_use]
+    pub fn missing_optional_features(&self, plugin: &Plugin) -> Vec<String> {
+        self.missing(plugin.optional_features().iter())
+    }
+
+    fn missing(&self, uris: impl Iterator<Item = Node>) -> Vec<String> {
+        uris.filter_map(|n| n.as_uri().map(str::to_string))
+            .filter(|uri| !self.contains(uri))
+            .collect()
+    }
+
+    fn insert(
+        &mut self,
+        uri: CString,
+        data: *mut c_void,
+        owner: Option<Arc<dyn Any + Send + Sync>>,
+    ) {
+        self.features.retain(|f| f.uri != uri);
+        let feature = LV2Feature {
+            uri: uri.as_ptr(),
+            data,
+        };
+        self.features.push(Arc::new(OwnedFeature {
+            uri,
+            feature,
+            _owner: owner,
+        }));
+    }
+}
+
+impl Debug for FeatureSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.uris()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_required_features() {
+        let world = crate::World::with_load_all();
+        for plugin in world.plugins() {
+            let features = FeatureSet::new();
+            let missing = features.missing_required_features(&plugin);
+            assert_eq!(missing.len(), plugin.required_features().count());
+
+            let features = FeatureSet::new().with_urid_mapper(&UridMapper::new());
+            let missing = features.missing_required_features(&plugin);
+            assert!(!missing.iter().any(|uri| uri == crate::urid::URID_MAP_URI));
+        }
+    }
+
+    #[test]
+    fn test_replace_feature() {
+        let features = FeatureSet::new()
+            .with_flag("http://lv2plug.in/ns/lv2core#isLive")
+            .with_flag("http://lv2plug.in/ns/lv2core#isLive");
+        assert_eq!(features.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_uri() {
+        let features = FeatureSet::new().try_with_flag("http://example.org/a\0b");
+        assert_eq!(
+            features.err(),
+            Some(Error::InvalidString("http://example.org/a\0b".to_string()))
+        );
+        let features = unsafe { FeatureSet::new().try_with_raw("\0", std::ptr::null_mut(), None) };
+        assert!(features.is_err());
+    }
+}
@@ -0,0 +1,773 @@
+use crate::atom::{Atom, SequenceWriter};
+use crate::buffer::PortBuffers;
+use crate::error::Error;
+use crate::feature::FeatureSet;
+use crate::instance::ActiveInstance;
+use crate::plugin::Plugin;
+use crate::port::PortKind;
+use crate::render::host_features;
+use crate::urid::UridMapper;
+use crate::worker::Worker;
+
+/// Identifies a plugin within a `Graph`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The index of the node in the order that it was added to the graph.
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A connection from an output port of one node to an input port of another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Connection {
+    /// The node that produces the data.
+    pub source: NodeId,
+    /// The symbol of the output port of `source`.
+    pub output: String,
+    /// The node that consumes the data.
+    pub destination: NodeId,
+    /// The symbol of the input port of `destination`.
+    pub input: String,
+}
+
+// The fields are dropped in order, so the instance is deactivated and freed
+// before the buffers and worker that it uses.
+struct GraphNode {
+    instance: ActiveInstance,
+    buffers: PortBuffers,
+    worker: Worker,
+    plugin: Plugin,
+}
+
+/// Runs several plugin instances that are connected to each other.
+///
+/// Each node owns the buffers of its ports. Before a node runs, the outputs
+/// that are connected to its inputs are copied into them. Audio and CV inputs
+/// with several connections receive the sum of the outputs. Control and atom
+/// inputs accept a single connection. Inputs without connections keep their
+/// values, so they can be set through `buffers_mut`. When the last connection
+/// into an input is removed, audio and CV inputs are cleared, control inputs
+/// are reset to the default value of the port and atom inputs are emptied.
+///
+/// The latency that plugins report is compensated by delaying the audio and
+/// CV connections of the paths with less latency, so that parallel paths stay
+/// aligned when they are mixed. The delays are allocated when connecting, up
+/// to [`max_latency`](#method.max_latency) frames.
+///
+/// # Example
+/// ```no_run
+/// use lilv::graph::Graph;
+///
+/// let world = lilv::World::with_load_all();
+/// let amp = world
+///     .plugins()
+///     .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+///     .unwrap();
+/// let mut graph = Graph::new(44100.0, 512);
+/// let first = graph.add_plugin(&amp).unwrap();
+/// let second = graph.add_plugin(&amp).unwrap();
+/// graph.connect(first, "out", second, "in").unwrap();
+/// graph.set_control(first, "gain", -6.0);
+/// graph.process(512);
+/// let output = graph.buffers(second).unwrap().by_symbol("out").unwrap();
+/// ```
+pub struct Graph {
+    sample_rate: f64,
+    block_size: usize,
+    mapper: UridMapper,
+    features: FeatureSet,
+    nodes: Vec<GraphNode>,
+    connections: Vec<Connection>,
+    // The delay line of each connection, in the same order as `connections`.
+    delays: Vec<DelayLine>,
+    order: Vec<usize>,
+    // The latency at the output of each node.
+    latencies: Vec<f32>,
+    compensate_latency: bool,
+    max_latency: usize,
+    scratch: Vec<f32>,
+}
+
+impl Graph {
+    /// Create an empty graph that processes at most `block_size` frames in
+    /// each call to `process`.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is 0.
+    #[must_use]
+    pub fn new(sample_rate: f64, block_size: usize) -> Graph {
+        Graph::with_features(sample_rate, block_size, FeatureSet::new())
+    }
+
+    /// Create an empty graph that provides `features` to its plugins. The
+    /// graph's own `urid:map`, `urid:unmap`, `log:log` and `opts:options`
+    /// features replace any that are in `features`. Each plugin also gets its
+    /// own `work:schedule` feature.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is 0.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn with_features(sample_rate: f64, block_size: usize, features: FeatureSet) -> Graph {
+        assert!(block_size > 0, "block_size must be greater than 0");
+        let mapper = UridMapper::new();
+        Graph {
+            sample_rate,
+            block_size,
+            features: host_features(features, &mapper, sample_rate, block_size),
+            mapper,
+            nodes: Vec::new(),
+            connections: Vec::new(),
+            delays: Vec::new(),
+            order: Vec::new(),
+            latencies: Vec::new(),
+            compensate_latency: true,
+            max_latency: sample_rate.round().max(0.0) as usize,
+            scratch: vec![0.0; block_size],
+        }
+    }
+
+    /// The maximum number of frames for `process`.
+    #[must_use]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The URID mapper that is provided to the plugins.
+    #[must_use]
+    pub fn urid_mapper(&self) -> &UridMapper {
+        &self.mapper
+    }
+
+    /// Instantiate and activate `plugin` and add it to the graph.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be instantiated.
+    pub fn add_plugin(&mut self, plugin: &Plugin) -> Result<NodeId, Error> {
+        let mut buffers = PortBuffers::new(plugin, &self.mapper, self.block_size);
+        let mut worker = Worker::new();
+        let features = self.features.clone().with_worker(&worker);
+        let mut instance = unsafe { plugin.try_instantiate(self.sample_rate, &features) }?;
+        unsafe {
+            buffers.connect(&mut instance);
+            worker.attach(&instance);
+        }
+        self.nodes.push(GraphNode {
+            instance: unsafe { instance.activate() },
+            buffers,
+            worker,
+            plugin: plugin.clone(),
+        });
+        self.latencies.push(0.0);
+        self.update_order()?;
+        Ok(NodeId(self.nodes.len() - 1))
+    }
+
+    /// The number of nodes in the graph.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the graph has no nodes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The plugin of `node`.
+    #[must_use]
+    pub fn plugin(&self, node: NodeId) -> Option<&Plugin> {
+        self.nodes.get(node.0).map(|n| &n.plugin)
+    }
+
+    /// The port buffers of `node`.
+    #[must_use]
+    pub fn buffers(&self, node: NodeId) -> Option<&PortBuffers> {
+        self.nodes.get(node.0).map(|n| &n.buffers)
+    }
+
+    /// The port buffers of `node`. Inputs that are not connected can be
+    /// written to before `process`.
+    #[must_use]
+    pub fn buffers_mut(&mut self, node: NodeId) -> Option<&mut PortBuffers> {
+        self.nodes.get_mut(node.0).map(|n| &mut n.buffers)
+    }
+
+    /// Set the control port with `symbol` of `node` to `value`. Returns
+    /// `false` if there is no such control port.
+    pub fn set_control(&mut self, node: NodeId, symbol: &str, value: f32) -> bool {
+        match self.buffers_mut(node) {
+            Some(buffers) => buffers.set_control(symbol, value),
+            None => false,
+        }
+    }
+
+    /// Enable or disable latency compensation. It is enabled by default.
+    pub fn set_latency_compensation(&mut self, enabled: bool) {
+        self.compensate_latency = enabled;
+    }
+
+    /// The largest difference in latency in frames that is compensated. This
+    /// is one second by default.
+    #[must_use]
+    pub fn max_latency(&self) -> usize {
+        self.max_latency
+    }
+
+    /// Set the largest difference in latency in frames that is compensated.
+    /// The delays of all the connections are allocated again and cleared, so
+    /// this should not be called while the graph is processing.
+    pub fn set_max_latency(&mut self, frames: usize) {
+        self.max_latency = frames;
+        for delay in &mut self.delays {
+            *delay = DelayLine::new(frames);
+        }
+    }
+
+    /// The latency in frames at the outputs of `node` after the last call to
+    /// `process`. This is the latency of the node plus the largest latency
+    /// of the nodes connected to its audio and CV inputs.
+    #[must_use]
+    pub fn latency(&self, node: NodeId) -> Option<f32> {
+        self.latencies.get(node.0).copied()
+    }
+
+    /// All the connections in the graph.
+    #[must_use]
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// The nodes in the order that they are processed.
+    #[must_use]
+    pub fn order(&self) -> Vec<NodeId> {
+        self.order.iter().copied().map(NodeId).collect()
+    }
+
+    /// Connect the port with symbol `output` of `source` to the port with
+    /// symbol `input` of `destination`.
+    ///
+    /// # Errors
+    /// Returns `Error::UnknownPort` if a port does not exist,
+    /// `Error::InvalidConnection` if the ports can not be connected and
+    /// `Error::GraphCycle` if the connection would create a cycle.
+    pub fn connect(
+        &mut self,
+        source: NodeId,
+        output: &str,
+        destination: NodeId,
+        input: &str,
+    ) -> Result<(), Error> {
+        let output_kind = self.port_kind(source, output, false)?;
+        let input_kind = self.port_kind(destination, input, true)?;
+        let compatible = match (output_kind, input_kind) {
+            (PortKind::Audio, PortKind::Audio)
+            | (PortKind::Audio, PortKind::Cv)
+            | (PortKind::Cv, PortKind::Audio)
+            | (PortKind::Cv, PortKind::Cv) => true,
+            (PortKind::Control, PortKind::Control) | (PortKind::Atom, PortKind::Atom) => !self
+                .connections
+                .iter()
+                .any(|c| c.destination == destination && c.input == input),
+            _ => false,
+        };
+        let connection = Connection {
+            source,
+            output: output.to_string(),
+            destination,
+            input: input.to_string(),
+        };
+        if !compatible || self.connections.contains(&connection) {
+            return Err(Error::InvalidConnection(format!(
+                "{}:{} -> {}:{}",
+                source.0, output, destination.0, input
+            )));
+        }
+        self.connections.push(connection);
+        self.delays.push(DelayLine::new(self.max_latency));
+        if let Err(err) = self.update_order() {
+            self.connections.pop();
+            self.delays.pop();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Remove the connection from `output` of `source` to `input` of
+    /// `destination`. Returns `false` if there was no such connection.
+    ///
+    /// If it was the last connection into `input`, the input is reset so the
+    /// plugin does not keep receiving the last block that was copied into it.
+    pub fn disconnect(
+        &mut self,
+        source: NodeId,
+        output: &str,
+        destination: NodeId,
+        input: &str,
+    ) -> bool {
+        let position = self.connections.iter().position(|c| {
+            c.source == source
+                && c.output == output
+                && c.destination == destination
+                && c.input == input
+        });
+        match position {
+            Some(position) => {
+                self.connections.remove(position);
+                self.delays.remove(position);
+                let is_connected = self
+                    .connections
+                    .iter()
+                    .any(|c| c.destination == destination && c.input == input);
+                if !is_connected {
+                    reset_input(&mut self.nodes[destination.0], input);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run all the nodes for `frames` frames in order of their connections.
+    ///
+    /// The input atom sequences are cleared after all the nodes have run, so
+    /// new events can be written before the next call.
+    ///
+    /// # Panics
+    /// Panics if `frames` is greater than the block size.
+    pub fn process(&mut self, frames: usize) {
+        assert!(
+            frames <= self.block_size,
+            "frames must not be greater than the block size"
+        );
+        for position in 0..self.order.len() {
+            let index = self.order[position];
+            let is_signal = |node: &GraphNode, symbol: &str| {
+                matches!(
+                    node.buffers.by_symbol(symbol).map(|b| b.kind()),
+                    Some(PortKind::Audio) | Some(PortKind::Cv)
+                )
+            };
+            let input_latency = self
+                .connections
+                .iter()
+                .filter(|c| c.destination.0 == index && is_signal(&self.nodes[index], &c.input))
+                .map(|c| self.latencies[c.source.0])
+                .fold(0.0, f32::max);
+
+            for (i, connection) in self.connections.iter().enumerate() {
+                if connection.destination.0 != index {
+                    continue;
+                }
+                // The first connection to an input replaces its contents and
+                // the others are added to it.
+                let summed = !matches!(
+                    self.connections
+                        .iter()
+                        .filter(|c| c.destination.0 == index && c.input == connection.input)
+                        .position(|c| c == connection),
+                    Some(0)
+                );
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let delay = if self.compensate_latency {
+                    (input_latency - self.latencies[connection.source.0])
+                        .round()
+                        .max(0.0) as usize
+                } else {
+                    0
+                };
+                self.delays[i].set_delay(delay);
+                let (source, destination) = pair_mut(&mut self.nodes, connection.source.0, index);
+                copy_port(
+                    &source.buffers,
+                    &connection.output,
+                    &mut destination.buffers,
+                    &connection.input,
+                    CopyOptions {
+                        frames,
+                        summed,
+                        delay: &mut self.delays[i],
+                        scratch: &mut self.scratch,
+                    },
+                );
+            }
+            let node = &mut self.nodes[index];
+            node.buffers.reset_atom_outputs();
+            unsafe { node.worker.run(&mut node.instance, frames) };
+            // The latency port is connected to `node.buffers`.
+            let latency = unsafe { node.instance.latency() }.unwrap_or(0.0).max(0.0);
+            self.latencies[index] = input_latency + latency;
+        }
+        for node in &mut self.nodes {
+            node.buffers.reset_atom_inputs();
+        }
+    }
+
+    fn port_kind(&self, node: NodeId, symbol: &str, is_input: bool) -> Result<PortKind, Error> {
+        let buffers = self
+            .buffers(node)
+            .ok_or_else(|| Error::InvalidConnection(format!("no node {}", node.0)))?;
+        match buffers.by_symbol(symbol) {
+            Some(buffer) if buffer.is_input() == is_input => Ok(buffer.kind()),
+            _ => Err(Error::UnknownPort(symbol.to_string())),
+        }
+    }
+
+    // Sort the nodes so that every node runs after the nodes that it is
+    // connected to. Nodes without dependencies run in the order that they were
+    // added.
+    fn update_order(&mut self) -> Result<(), Error> {
+        let mut incoming = vec![0; self.nodes.len()];
+        for c in &self.connections {
+            incoming[c.destination.0] += 1;
+        }
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut done = vec![false; self.nodes.len()];
+        while order.len() < self.nodes.len() {
+            let next = (0..self.nodes.len()).find(|&i| !done[i] && incoming[i] == 0);
+            let next = next.ok_or(Error::GraphCycle)?;
+            done[next] = true;
+            order.push(next);
+            for c in self.connections.iter().filter(|c| c.source.0 == next) {
+                incoming[c.destination.0] -= 1;
+            }
+        }
+        self.order = order;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Graph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Graph")
+            .field("sample_rate", &self.sample_rate)
+            .field("block_size", &self.block_size)
+            .field("nodes", &self.nodes.len())
+            .field("connections", &self.connections)
+            .finish()
+    }
+}
+
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&T, &mut T) {
+    assert_ne!(a, b);
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&right[0], &mut left[b])
+    }
+}
+
+// A delay of a whole number of frames, up to the capacity that it was
+// allocated with. Changing the delay does not allocate.
+struct DelayLine {
+    buffer: Vec<f32>,
+    delay: usize,
+    position: usize,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> DelayLine {
+        DelayLine {
+            buffer: vec![0.0; capacity],
+            delay: 0,
+            position: 0,
+        }
+    }
+
+    // Change the delay, limited to the capacity. The delay line is cleared if
+    // the delay changed.
+    fn set_delay(&mut self, delay: usize) {
+        let delay = delay.min(self.buffer.len());
+        if delay != self.delay {
+            self.buffer[..delay].iter_mut().for_each(|s| *s = 0.0);
+            self.delay = delay;
+            self.position = 0;
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        if self.delay == 0 {
+            return;
+        }
+        for sample in samples {
+            std::mem::swap(sample, &mut self.buffer[self.position]);
+            self.position = (self.position + 1) % self.delay;
+        }
+    }
+}
+
+struct CopyOptions<'a> {
+    frames: usize,
+    summed: bool,
+    delay: &'a mut DelayLine,
+    scratch: &'a mut [f32],
+}
+
+fn copy_port(
+    source: &PortBuffers,
+    output: &str,
+    destination: &mut PortBuffers,
+    input: &str,
+    options: CopyOptions<'_>,
+) {
+    let frames = options.frames;
+    let urids = *destination.atom_urids();
+    let (from, to) = match (source.by_symbol(output), destination.by_symbol_mut(input)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return,
+    };
+    if let (Some(from), Some(to)) = (from.samples(), to.samples_mut()) {
+        let delayed = &mut options.scratch[..frames];
+        delayed.copy_from_slice(&from[..frames]);
+        options.delay.process(delayed);
+        for (to, from) in to[..frames].iter_mut().zip(delayed.iter()) {
+            *to = if options.summed { *to + *from } else { *from };
+        }
+    } else if let (Some(from), Some(to)) = (from.control(), to.control_mut()) {
+        *to = from;
+    } else if let (Some(from), Some(to)) = (from.atom_bytes(), to.atom_bytes_mut()) {
+        // Copy the header and events of the output sequence. Anything else,
+        // like the chunk of a plugin that did not write its output, becomes an
+        // empty sequence.
+        let len = match Atom::from_bytes(from) {
+            Some(atom) if atom.type_urid == urids.sequence => 8 + atom.body.len(),
+            _ => 0,
+        };
+        if len > 0 && len <= to.len() {
+            to[..len].copy_from_slice(&from[..len]);
+        } else {
+            let _ = SequenceWriter::new(to, &urids);
+        }
+    }
+}
+
+// Clear an input that is no longer connected.
+fn reset_input(node: &mut GraphNode, input: &str) {
+    let urids = *node.buffers.atom_urids();
+    let buffer = match node.buffers.by_symbol_mut(input) {
+        Some(buffer) => buffer,
+        None => return,
+    };
+    let index = buffer.index();
+    if let Some(samples) = buffer.samples_mut() {
+        samples.fill(0.0);
+    } else if let Some(value) = buffer.control_mut() {
+        *value = node
+            .plugin
+            .port_by_index(index)
+            .and_then(|port| port.range().default)
+            .and_then(|default| default.as_float())
+            .unwrap_or(0.0);
+    } else if let Some(bytes) = buffer.atom_bytes_mut() {
+        let _ = SequenceWriter::new(bytes, &urids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_amp_chain() {
+        let world = World::with_load_all();
+        let amp = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let mut graph = Graph::new(44100.0, 64);
+        let first = graph.add_plugin(&amp).unwrap();
+        let second = graph.add_plugin(&amp).unwrap();
+        graph.connect(second, "out", first, "in").unwrap();
+        assert_eq!(graph.order(), vec![second, first]);
+        assert_eq!(
+            graph.connect(first, "out", second, "in"),
+            Err(Error::GraphCycle)
+        );
+        assert!(graph.connect(first, "gain", second, "in").is_err());
+
+        graph.set_control(first, "gain", 0.0);
+        graph.set_control(second, "gain", 0.0);
+        graph
+            .buffers_mut(second)
+            .unwrap()
+            .by_symbol_mut("in")
+            .unwrap()
+            .samples_mut()
+            .unwrap()
+            .iter_mut()
+            .for_each(|s| *s = 0.5);
+        graph.process(64);
+        assert_eq!(graph.latency(first), Some(0.0));
+        let out = graph.buffers(first).unwrap().by_symbol("out").unwrap();
+        assert!(out
+            .samples()
+            .unwrap()
+            .iter()
+            .all(|s| (*s - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_disconnect_resets_inputs() {
+        let world = World::with_test_bundle();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://example.org/lilv-test"))
+            .unwrap();
+        let mut graph = Graph::new(44100.0, 8);
+        let source = graph.add_plugin(&plugin).unwrap();
+        let destination = graph.add_plugin(&plugin).unwrap();
+        graph
+            .connect(source, "out_left", destination, "in_left")
+            .unwrap();
+        graph
+            .connect(source, "latency", destination, "level")
+            .unwrap();
+        graph
+            .buffers_mut(source)
+            .unwrap()
+            .by_symbol_mut("in_left")
+            .unwrap()
+            .samples_mut()
+            .unwrap()
+            .iter_mut()
+            .for_each(|s| *s = 0.5);
+        graph.process(8);
+        let buffers = graph.buffers(destination).unwrap();
+        assert_eq!(buffers.control("level"), Some(4.0));
+        assert!(buffers
+            .by_symbol("in_left")
+            .unwrap()
+            .samples()
+            .unwrap()
+            .iter()
+            .any(|s| *s != 0.0));
+
+        assert!(graph.disconnect(source, "out_left", destination, "in_left"));
+        assert!(graph.disconnect(source, "latency", destination, "level"));
+        let buffers = graph.buffers(destination).unwrap();
+        assert_eq!(buffers.control("level"), Some(0.0));
+        assert!(buffers
+            .by_symbol("in_left")
+            .unwrap()
+            .samples()
+            .unwrap()
+            .iter()
+            .all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_copy_port_only_forwards_sequences() {
+        let world = World::with_load_all();
+        let fifths = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-fifths"))
+            .unwrap();
+        let mapper = UridMapper::new();
+        let mut source = PortBuffers::new(&fifths, &mapper, 8);
+        let mut destination = PortBuffers::new(&fifths, &mapper, 8);
+        let mut delay = DelayLine::new(0);
+        let mut scratch = [0.0; 8];
+        let mut copy = |source: &PortBuffers, destination: &mut PortBuffers| {
+            let options = CopyOptions {
+                frames: 8,
+                summed: false,
+                delay: &mut delay,
+                scratch: &mut scratch,
+            };
+            copy_port(source, "out", destination, "in", options);
+        };
+
+        source
+            .sequence_writer("out")
+            .unwrap()
+            .write_midi(2, &[0x90, 60, 100]);
+        copy(&source, &mut destination);
+        assert_eq!(destination.sequence("in").unwrap().count(), 1);
+
+        // An output that the plugin did not write still holds a chunk.
+        source.reset_atom_outputs();
+        copy(&source, &mut destination);
+        assert_eq!(destination.sequence("in").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_delay_line() {
+        let mut delay = DelayLine::new(4);
+        let mut samples = [1.0, 2.0, 3.0, 4.0, 5.0];
+        delay.process(&mut samples);
+        assert_eq!(samples, [1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        delay.set_delay(3);
+        delay.process(&mut samples);
+        assert_eq!(samples, [0.0, 0.0, 0.0, 1.0, 2.0]);
+        let mut samples = [6.0, 7.0];
+        delay.process(&mut samples);
+        assert_eq!(samples, [3.0, 4.0]);
+
+        // The delay is limited to the capacity and changing it clears it.
+        delay.set_delay(10);
+        let mut samples = [8.0, 9.0, 10.0, 11.0, 12.0];
+        delay.process(&mut samples);
+        assert_eq!(samples, [0.0, 0.0, 0.0, 0.0, 8.0]);
+    }
+
+    #[test]
+    fn test_parallel_paths_are_aligned() {
+        let world = World::with_load_all();
+        let amp = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let mut graph = Graph::new(44100.0, 8);
+        let direct = graph.add_plugin(&amp).unwrap();
+        let late = graph.add_plugin(&amp).unwrap();
+        let mix = graph.add_plugin(&amp).unwrap();
+        graph.connect(direct, "out", mix, "in").unwrap();
+        graph.connect(late, "out", mix, "in").unwrap();
+        for node in [direct, late, mix] {
+            graph.set_control(node, "gain", 0.0);
+        }
+
+        // `late` stands in for a plugin with a latency of 3 frames. eg-amp does
+        // not delay, so its input is written 3 frames late and it reports the
+        // latency through a value that its latency port is connected to.
+        let reported = Box::new(3.0_f32);
+        let instance = graph.nodes[late.0].instance.instance_mut();
+        instance.latency_port = Some(usize::MAX);
+        instance.latency = &*reported;
+
+        let mut mixed = Vec::new();
+        for block in 0..2 {
+            for (node, frame) in [(direct, 6), (late, 9)] {
+                let samples = graph
+                    .buffers_mut(node)
+                    .unwrap()
+                    .by_symbol_mut("in")
+                    .unwrap()
+                    .samples_mut()
+                    .unwrap();
+                for (i, s) in samples.iter_mut().enumerate() {
+                    *s = if block * 8 + i == frame { 0.5 } else { 0.0 };
+                }
+            }
+            graph.process(8);
+            let out = graph.buffers(mix).unwrap().by_symbol("out").unwrap();
+            mixed.extend_from_slice(out.samples().unwrap());
+        }
+        assert_eq!(graph.latency(mix), Some(3.0));
+        for (frame, sample) in mixed.iter().enumerate() {
+            let expected = if frame == 9 { 1.0 } else { 0.0 };
+            assert!((sample - expected).abs() < 1e-6, "frame {}", frame);
+        }
+        drop(graph);
+        drop(reported);
+    }
+}
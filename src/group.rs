@@ -0,0 +1,205 @@
+use crate::node::Node;
+use crate::plugin::Plugin;
+use crate::port::Port;
+use crate::world::World;
+
+/// The URI for the `pg:left` channel designation.
+pub const PG_LEFT: &str = "http://lv2plug.in/ns/ext/port-groups#left";
+/// The URI for the `pg:right` channel designation.
+pub const PG_RIGHT: &str = "http://lv2plug.in/ns/ext/port-groups#right";
+/// The URI for the `pg:center` channel designation.
+pub const PG_CENTER: &str = "http://lv2plug.in/ns/ext/port-groups#center";
+/// The URI for the `pg:side` channel designation.
+pub const PG_SIDE: &str = "http://lv2plug.in/ns/ext/port-groups#side";
+/// The URI for the `pg:sideLeft` channel designation.
+pub const PG_SIDE_LEFT: &str = "http://lv2plug.in/ns/ext/port-groups#sideLeft";
+/// The URI for the `pg:sideRight` channel designation.
+pub const PG_SIDE_RIGHT: &str = "http://lv2plug.in/ns/ext/port-groups#sideRight";
+/// The URI for the `pg:rearLeft` channel designation.
+pub const PG_REAR_LEFT: &str = "http://lv2plug.in/ns/ext/port-groups#rearLeft";
+/// The URI for the `pg:rearRight` channel designation.
+pub const PG_REAR_RIGHT: &str = "http://lv2plug.in/ns/ext/port-groups#rearRight";
+/// The URI for the `pg:lowFrequencyEffects` channel designation.
+pub const PG_LOW_FREQUENCY_EFFECTS: &str =
+    "http://lv2plug.in/ns/ext/port-groups#lowFrequencyEffects";
+
+const PG_GROUP: &str = "http://lv2plug.in/ns/ext/port-groups#group";
+const PG_MAIN_INPUT: &str = "http://lv2plug.in/ns/ext/port-groups#mainInput";
+const PG_MAIN_OUTPUT: &str = "http://lv2plug.in/ns/ext/port-groups#mainOutput";
+
+/// A group of ports, for example the channels of a stereo input.
+#[derive(Clone, Debug)]
+pub struct PortGroup {
+    /// The URI of the group.
+    pub uri: Node,
+    /// The symbol of the group.
+    pub symbol: Option<String>,
+    /// The human readable name of the group.
+    pub name: Option<String>,
+    /// The classes of the group, for example `pg:StereoGroup`.
+    pub classes: Vec<Node>,
+    /// `true` if the group is the `pg:mainInput` of the plugin.
+    pub is_main_input: bool,
+    /// `true` if the group is the `pg:mainOutput` of the plugin.
+    pub is_main_output: bool,
+    /// The ports in the group, in order of their index.
+    pub ports: Vec<Port>,
+}
+
+impl PortGroup {
+    /// Get the port in the group with the channel `designation`, for example
+    /// `PG_LEFT`.
+    #[must_use]
+    pub fn port_for_channel(&self, designation: &str) -> Option<&Port> {
+        self.ports
+            .iter()
+            .find(|port| matches!(port.designation(), Some(d) if d.as_uri() == Some(designation)))
+    }
+
+    /// The channel designations of the ports in the group, in the same order as
+    /// `ports`.
+    #[must_use]
+    pub fn channels(&self) -> Vec<Option<Node>> {
+        self.ports.iter().map(Port::designation).collect()
+    }
+}
+
+impl Plugin {
+    /// Get all the port groups of the plugin, in order of their first port.
+    #[must_use]
+    pub fn port_groups(&self) -> Vec<PortGroup> {
+        let world = World::from_life(self.life.clone());
+        let mut groups: Vec<(Node, Vec<Port>)> = Vec::new();
+        for port in self.iter_ports() {
+            let uri = match port.group() {
+                Some(uri) => uri,
+                None => continue,
+            };
+            match groups.iter_mut().find(|(group, _)| *group == uri) {
+                Some((_, ports)) => ports.push(port),
+                None => groups.push((uri, vec![port])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(uri, ports)| self.new_port_group(&world, uri, ports))
+            .collect()
+    }
+
+    /// Get the `pg:mainInput` group of the plugin.
+    #[must_use]
+    pub fn main_input_group(&self) -> Option<PortGroup> {
+        self.main_group(PG_MAIN_INPUT)
+    }
+
+    /// Get the `pg:mainOutput` group of the plugin.
+    #[must_use]
+    pub fn main_output_group(&self) -> Option<PortGroup> {
+        self.main_group(PG_MAIN_OUTPUT)
+    }
+
+    // Build only the group that is the value of `predicate`.
+    fn main_group(&self, predicate: &str) -> Option<PortGroup> {
+        let world = World::from_life(self.life.clone());
+        let uri = self.value(&world.new_uri(predicate)).iter().next()?;
+        let ports: Vec<Port> = self
+            .iter_ports()
+            .filter(|port| port.group().as_ref() == Some(&uri))
+            .collect();
+        if ports.is_empty() {
+            return None;
+        }
+        Some(self.new_port_group(&world, uri, ports))
+    }
+
+    fn new_port_group(&self, world: &World, uri: Node, ports: Vec<Port>) -> PortGroup {
+        let uris = world.uris();
+        let get_str = |pred: &Node| {
+            world
+                .get(Some(&uri), Some(pred), None)
+                .and_then(|n| n.as_str().map(str::to_string))
+        };
+        let is_main = |pred: &str| self.value(&world.new_uri(pred)).contains(&uri);
+        PortGroup {
+            symbol: get_str(&uris.lv2_symbol),
+            name: get_str(&uris.lv2_name).or_else(|| get_str(&uris.rdfs_label)),
+            classes: world
+                .find_nodes(Some(&uri), &uris.rdf_type, None)
+                .iter()
+                .collect(),
+            is_main_input: is_main(PG_MAIN_INPUT),
+            is_main_output: is_main(PG_MAIN_OUTPUT),
+            ports,
+            uri,
+        }
+    }
+}
+
+impl Port {
+    /// Get the `pg:group` that the port belongs to.
+    #[must_use]
+    pub fn group(&self) -> Option<Node> {
+        let world = World::from_life(self.plugin.life.clone());
+        self.get(&world.new_uri(PG_GROUP))
+    }
+
+    /// Get the `lv2:designation` of the port. For ports in a group, this is
+    /// the channel of the port, for example `pg:left`.
+    #[must_use]
+    pub fn designation(&self) -> Option<Node> {
+        let world = World::from_life(self.plugin.life.clone());
+        self.get(&world.uris().lv2_designation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amp_port_groups() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        assert!(plugin.port_groups().is_empty());
+        assert!(plugin.main_input_group().is_none());
+        for port in plugin.iter_ports() {
+            assert!(port.group().is_none());
+        }
+    }
+
+    #[test]
+    fn test_stereo_port_groups() {
+        let world = World::with_test_bundle();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://example.org/lilv-test"))
+            .unwrap();
+        let groups = plugin.port_groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].symbol.as_deref(), Some("in"));
+        assert_eq!(groups[0].name.as_deref(), Some("Input"));
+        assert!(groups[0].is_main_input && !groups[0].is_main_output);
+        assert!(groups[0]
+            .classes
+            .iter()
+            .any(|c| c.as_uri() == Some("http://lv2plug.in/ns/ext/port-groups#StereoGroup")));
+        assert_eq!(groups[1].symbol.as_deref(), Some("out"));
+        assert!(groups[1].is_main_output);
+
+        let input = plugin.main_input_group().unwrap();
+        assert_eq!(input.uri, groups[0].uri);
+        let left = input.port_for_channel(PG_LEFT).unwrap();
+        let right = input.port_for_channel(PG_RIGHT).unwrap();
+        assert_eq!((left.index(), right.index()), (0, 1));
+        assert!(input.port_for_channel(PG_CENTER).is_none());
+
+        let output = plugin.main_output_group().unwrap();
+        let indices: Vec<usize> = output.ports.iter().map(Port::index).collect();
+        assert_eq!(indices, vec![2, 3]);
+        let level = plugin.port_by_index(4).unwrap();
+        assert!(level.group().is_none());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::node::{Node, Nodes};
+use crate::plugin::Plugin;
+use crate::port::{Port, PortDirection, PortFlags, PortKind};
+use crate::ui::UI;
+use crate::world::World;
+
+/// An owned description of a plugin.
+///
+/// Unlike `Plugin`, the description does not reference the world, so it can be
+/// kept after the world is dropped and sent to other threads. With the `serde`
+/// feature, it can also be serialized.
+///
+/// # Example
+/// ```
+/// let world = lilv::World::with_load_all();
+/// let infos: Vec<_> = world.plugins().iter().map(|p| p.info()).collect();
+/// drop(world);
+/// for info in &infos {
+///     println!("{} ({} ports)", info.name, info.ports.len());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginInfo {
+    /// The URI of the plugin.
+    pub uri: String,
+    /// The human readable name of the plugin.
+    pub name: String,
+    /// The URI of the class of the plugin, for example `lv2:DelayPlugin`.
+    pub class_uri: Option<String>,
+    /// The human readable label of the class of the plugin.
+    pub class_label: String,
+    /// The URI of the bundle that contains the plugin.
+    pub bundle_uri: String,
+    /// The URI of the shared library of the plugin.
+    pub library_uri: Option<String>,
+    /// The name of the author of the plugin.
+    pub author_name: Option<String>,
+    /// The email address of the author of the plugin.
+    pub author_email: Option<String>,
+    /// The homepage of the author of the plugin.
+    pub author_homepage: Option<String>,
+    /// The URIs of the features that the plugin requires.
+    pub required_features: Vec<String>,
+    /// The URIs of the features that the plugin can use if they are provided.
+    pub optional_features: Vec<String>,
+    /// The URIs of the extension data that the plugin provides.
+    pub extension_data: Vec<String>,
+    /// The index of the port that reports the latency of the plugin.
+    pub latency_port: Option<usize>,
+    /// The ports of the plugin, in order of their index.
+    pub ports: Vec<PortInfo>,
+    /// The UIs of the plugin.
+    pub uis: Vec<UiInfo>,
+    /// The presets of the plugin. Their resources are not loaded, so the
+    /// labels and banks are only known if the manifests provide them.
+    pub presets: Vec<PresetInfo>,
+}
+
+impl PluginInfo {
+    /// Get the port with `symbol`.
+    #[must_use]
+    pub fn port_by_symbol(&self, symbol: &str) -> Option<&PortInfo> {
+        self.ports.iter().find(|p| p.symbol == symbol)
+    }
+}
+
+/// An owned description of a port.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortInfo {
+    /// The index of the port.
+    pub index: usize,
+    /// The symbol of the port.
+    pub symbol: String,
+    /// The human readable name of the port.
+    pub name: Option<String>,
+    /// The direction of the port.
+    pub direction: PortDirection,
+    /// The kind of data that the port carries.
+    pub kind: PortKind,
+    /// The common properties of the port.
+    pub flags: PortFlags,
+    /// The URIs of all the classes of the port.
+    pub classes: Vec<String>,
+    /// The URIs of all the properties of the port.
+    pub properties: Vec<String>,
+    /// The default value of the port.
+    pub default: Option<f32>,
+    /// The minimum value of the port.
+    pub minimum: Option<f32>,
+    /// The maximum value of the port.
+    pub maximum: Option<f32>,
+    /// The labelled values of the port.
+    pub scale_points: Vec<ScalePointInfo>,
+    /// The `lv2:designation` of the port.
+    pub designation: Option<String>,
+    /// The `pg:group` that the port belongs to.
+    pub group: Option<String>,
+}
+
+/// An owned labelled value of a port.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScalePointInfo {
+    /// The human readable label of the value.
+    pub label: String,
+    /// The value.
+    pub value: f32,
+}
+
+/// An owned description of a plugin UI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UiInfo {
+    /// The URI of the UI.
+    pub uri: String,
+    /// The URIs of the classes of the UI, for example `ui:X11UI`.
+    pub classes: Vec<String>,
+    /// The URI of the bundle that contains the UI.
+    pub bundle_uri: Option<String>,
+    /// The URI of the shared library of the UI.
+    pub binary_uri: Option<String>,
+}
+
+/// An owned description of a preset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PresetInfo {
+    /// The URI of the preset.
+    pub uri: String,
+    /// The human readable label of the preset.
+    pub label: Option<String>,
+    /// The URI of the bank the preset belongs to.
+    pub bank: Option<String>,
+}
+
+impl Plugin {
+    /// Get an owned description of the plugin.
+    #[must_use]
+    pub fn info(&self) -> PluginInfo {
+        let class = self.class();
+        PluginInfo {
+            uri: string(&self.uri()).unwrap_or_default(),
+            name: string(&self.name()).unwrap_or_default(),
+            class_uri: class.uri().as_ref().and_then(string),
+            class_label: string(&class.label()).unwrap_or_default(),
+            bundle_uri: string(&self.bundle_uri()).unwrap_or_default(),
+            library_uri: self.library_uri().as_ref().and_then(string),
+            author_name: self.author_name().as_ref().and_then(string),
+            author_email: self.author_email().as_ref().and_then(string),
+            author_homepage: self.author_homepage().as_ref().and_then(string),
+            required_features: strings(&self.required_features()),
+            optional_features: strings(&self.optional_features()),
+            extension_data: self
+                .extension_data()
+                .map(|n| strings(&n))
+                .unwrap_or_default(),
+            latency_port: self.latency_port_index(),
+            ports: self.iter_ports().map(|p| p.info()).collect(),
+            uis: self
+                .uis()
+                .map(|uis| uis.iter().map(|ui| ui.info()).collect())
+                .unwrap_or_default(),
+            presets: self.preset_infos(),
+        }
+    }
+
+    // Describe the presets from the data that is already loaded. Unlike
+    // `Plugin::presets`, the resources of the presets are not loaded, so the
+    // labels and banks are only known if the manifests provide them.
+    fn preset_infos(&self) -> Vec<PresetInfo> {
+        let world = World::from_life(self.life.clone());
+        let uris = world.uris();
+        world
+            .find_nodes(None, &uris.lv2_applies_to, Some(&self.uri()))
+            .iter()
+            .filter(|uri| world.ask(Some(uri), Some(&uris.rdf_type), Some(&uris.pset_preset)))
+            .map(|uri| PresetInfo {
+                uri: string(&uri).unwrap_or_default(),
+                label: world
+                    .get(Some(&uri), Some(&uris.rdfs_label), None)
+                    .as_ref()
+                    .and_then(string),
+                bank: world
+                    .get(Some(&uri), Some(&uris.pset_bank), None)
+                    .as_ref()
+                    .and_then(string),
+            })
+            .collect()
+    }
+}
+
+impl Port {
+    /// Get an owned description of the port.
+    #[must_use]
+    pub fn info(&self) -> PortInfo {
+        let range = self.range();
+        PortInfo {
+            index: self.index(),
+            symbol: self.symbol().as_ref().and_then(string).unwrap_or_default(),
+            name: self.name().as_ref().and_then(string),
+            direction: self.direction(),
+            kind: self.kind(),
+            flags: self.flags(),
+            classes: strings(&self.classes()),
+            properties: strings(&self.properties()),
+            default: range.default.and_then(|n| n.as_float()),
+            minimum: range.minimum.and_then(|n| n.as_float()),
+            maximum: range.maximum.and_then(|n| n.as_float()),
+            scale_points: self
+                .scale_points()
+                .iter()
+                .filter_map(|point| {
+                    Some(ScalePointInfo {
+                        label: string(&point.label())?,
+                        value: point.value().as_float()?,
+                    })
+                })
+                .collect(),
+            designation: self.designation().as_ref().and_then(string),
+            group: self.group().as_ref().and_then(string),
+        }
+    }
+}
+
+impl UI {
+    /// Get an owned description of the UI.
+    #[must_use]
+    pub fn info(&self) -> UiInfo {
+        UiInfo {
+            uri: string(&self.uri()).unwrap_or_default(),
+            classes: strings(&self.classes()),
+            bundle_uri: self.bundle_uri().as_ref().and_then(string),
+            binary_uri: self.binary_uri().as_ref().and_then(string),
+        }
+    }
+}
+
+fn string(node: &Node) -> Option<String> {
+    node.as_str().map(str::to_string)
+}
+
+fn strings(nodes: &Nodes) -> Vec<String> {
+    nodes.iter().filter_map(|n| string(&n)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::World;
+
+    #[test]
+    fn test_amp_info() {
+        let world = World::with_load_all();
+        let info = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap()
+            .info();
+        drop(world);
+        let handle = std::thread::spawn(move || info);
+        let info = handle.join().unwrap();
+        assert_eq!(info.uri, "http://lv2plug.in/plugins/eg-amp");
+        assert_eq!(info.ports.len(), 3);
+        let gain = info.port_by_symbol("gain").unwrap();
+        assert_eq!(gain.minimum, Some(-90.0));
+        assert_eq!(gain.maximum, Some(24.0));
+    }
+
+    #[test]
+    fn test_preset_info_does_not_load_presets() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-params"))
+            .unwrap();
+        let info = plugin.info();
+        assert!(!info.presets.is_empty());
+        let uris = world.uris();
+        for preset in &info.presets {
+            // The state of a preset is only in its resource.
+            let uri = world.new_uri(&preset.uri);
+            assert!(!world.ask(Some(&uri), Some(&uris.state_state), None));
+        }
+        let mut loaded: Vec<_> = plugin
+            .presets()
+            .iter()
+            .map(|p| p.uri.as_uri().unwrap().to_string())
+            .collect();
+        let mut listed: Vec<_> = info.presets.iter().map(|p| p.uri.clone()).collect();
+        loaded.sort();
+        listed.sort();
+        assert_eq!(listed, loaded);
+    }
+}
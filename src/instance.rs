@@ -1,14 +1,41 @@
+use crate::error::Error;
+use crate::feature::FeatureSet;
+use crate::world::Life;
 use lilv_sys as lib;
 use lv2_raw::core::LV2Descriptor;
 use lv2_raw::core::LV2Handle;
 use std::convert::TryFrom;
-use std::ffi::CStr;
+use std::ffi::{c_void, CStr};
 use std::ptr::NonNull;
+use std::sync::Arc;
 
 /// An LV2 plugin instance.
 #[allow(clippy::module_name_repetitions)]
 pub struct Instance {
     pub(crate) inner: NonNull<lib::LilvInstanceImpl>,
+    // Keeps the data for the features passed to the instance alive.
+    pub(crate) features: FeatureSet,
+    // The index of the port that reports the latency and the data that it is
+    // connected to.
+    pub(crate) latency_port: Option<usize>,
+    pub(crate) latency: *const f32,
+    pub(crate) ports: Vec<PortConnection>,
+    // Keeps the world alive, since freeing the instance closes its library
+    // through the world.
+    pub(crate) life: Arc<Life>,
+}
+
+// The data that a port is connected to, or null if it is not connected.
+pub(crate) struct PortConnection {
+    pub(crate) symbol: String,
+    pub(crate) optional: bool,
+    pub(crate) data: *const c_void,
+}
+
+impl PortConnection {
+    fn is_missing(&self) -> bool {
+        self.data.is_null() && !self.optional
+    }
 }
 
 /// An LV2 plugin instance that has been activated and is ready to process data.
@@ -64,6 +91,12 @@ impl Instance {
     /// # Safety
     /// Connecting a port calls a plugin's code, which itself may be unsafe.
     pub unsafe fn connect_port_mut<T>(&mut self, port_index: usize, data: *mut T) {
+        if self.latency_port == Some(port_index) {
+            self.latency = data.cast();
+        }
+        if let Some(port) = self.ports.get_mut(port_index) {
+            port.data = data.cast();
+        }
         match u32::try_from(port_index) {
             Ok(port_index) => {
                 lib::lilv_instance_connect_port(self.inner.as_ptr(), port_index, data.cast())
@@ -87,6 +120,62 @@ impl Instance {
         self.connect_port_mut(port_index, data as *mut T);
     }
 
+    /// Returns `true` if the port with `port_index` is connected to data
+    /// that is not null.
+    #[must_use]
+    pub fn is_connected(&self, port_index: usize) -> bool {
+        match self.ports.get(port_index) {
+            Some(port) => !port.data.is_null(),
+            None => false,
+        }
+    }
+
+    /// The data that the port with `port_index` is connected to.
+    pub(crate) fn connected_data(&self, port_index: usize) -> Option<*const c_void> {
+        self.ports.get(port_index).map(|port| port.data)
+    }
+
+    /// The symbols of the ports that are not connected and are not
+    /// `lv2:connectionOptional`.
+    #[must_use]
+    pub fn unconnected_ports(&self) -> Vec<String> {
+        self.ports
+            .iter()
+            .filter(|p| p.is_missing())
+            .map(|p| p.symbol.clone())
+            .collect()
+    }
+
+    // Fails with the unconnected ports. The list is only built on failure so
+    // that the check does not allocate.
+    pub(crate) fn check_connected(&self) -> Result<(), Error> {
+        if self.ports.iter().any(PortConnection::is_missing) {
+            Err(Error::UnconnectedPorts(self.unconnected_ports()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Activate a plugin instance, like `activate`, after checking that all the
+    /// ports that are not `lv2:connectionOptional` are connected.
+    ///
+    /// The instance does not own the data that its ports are connected to, so
+    /// this is still unsafe. Use `Processor::try_process` to run an instance
+    /// without `unsafe`.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe. Only null connections are
+    /// detected, so the data that the ports are connected to must still be
+    /// valid.
+    ///
+    /// # Errors
+    /// Returns `Error::UnconnectedPorts` with the symbols of the ports that
+    /// are not connected.
+    pub unsafe fn try_activate(self) -> Result<ActiveInstance, Error> {
+        self.check_connected()?;
+        Ok(self.activate())
+    }
+
     /// Activate a plugin instance.
     ///
     /// This resets all state information in the plugin except for port
@@ -123,6 +212,19 @@ impl Instance {
         unsafe { d.as_ref() }
     }
 
+    /// Get the features that were passed to the instance with
+    /// `Plugin::instantiate_with_features`.
+    #[must_use]
+    pub fn features(&self) -> &FeatureSet {
+        &self.features
+    }
+
+    /// The index of the port that reports the latency of the plugin.
+    #[must_use]
+    pub fn latency_port(&self) -> Option<usize> {
+        self.latency_port
+    }
+
     /// Get the raw handle for the plugin instance.
     #[must_use]
     pub fn handle(&self) -> LV2Handle {
@@ -132,6 +234,7 @@ impl Instance {
 
 impl Drop for Instance {
     fn drop(&mut self) {
+        let _life = self.life.inner.lock();
         unsafe { lib::lilv_instance_free(self.inner.as_ptr().cast()) };
     }
 }
@@ -150,6 +253,46 @@ impl ActiveInstance {
         lib::lilv_instance_run(self.instance().inner.as_ptr(), sample_count);
     }
 
+    /// Run the plugin instance for `sample_count` frames, like `run`, after
+    /// checking that all the ports that are not `lv2:connectionOptional` are
+    /// still connected.
+    ///
+    /// The instance does not own the data that its ports are connected to, so
+    /// this is still unsafe. Use `Processor::try_process` to run an instance
+    /// without `unsafe`.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe. Only null connections are
+    /// detected, so the data that the ports are connected to must still be
+    /// valid and hold at least `sample_count` frames.
+    ///
+    /// # Errors
+    /// Returns `Error::UnconnectedPorts` with the symbols of the ports that
+    /// are not connected.
+    pub unsafe fn try_run(&mut self, sample_count: usize) -> Result<(), Error> {
+        self.inner.check_connected()?;
+        self.run(sample_count);
+        Ok(())
+    }
+
+    /// The latency of the plugin in frames, as reported by its latency port
+    /// after the last call to `run`. Returns `None` if the plugin does not
+    /// report its latency or the latency port is not connected.
+    ///
+    /// # Safety
+    /// The data that the latency port is connected to must still be valid and
+    /// must not be written to while it is read.
+    #[must_use]
+    pub unsafe fn latency(&self) -> Option<f32> {
+        self.inner.latency_port?;
+        let latency = self.inner.latency;
+        if latency.is_null() {
+            None
+        } else {
+            Some(*latency)
+        }
+    }
+
     /// Deactivate the plugin instance.
     ///
     /// Note: This will reset all state information except for port connections.
@@ -159,13 +302,11 @@ impl ActiveInstance {
     #[must_use]
     pub unsafe fn deactivate(self) -> Option<Instance> {
         let mut active_instance = self;
-        let instance = active_instance
-            .deactive_impl()
-            .map(|i| Instance { inner: i })?;
+        active_instance.deactive_impl()?;
         // Prevent running deactivate twice since we manually called the drop
         // side-effects with `deactivate_impl`..
-        std::mem::forget(active_instance);
-        Some(instance)
+        let active_instance = std::mem::ManuallyDrop::new(active_instance);
+        Some(std::ptr::read(&active_instance.inner))
     }
 
     /// Get the underlying instance.
@@ -234,4 +375,28 @@ mod tests {
             active_instance.run(1);
         }
     }
+
+    #[test]
+    fn test_try_activate() {
+        let world = crate::World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let mapper = crate::urid::UridMapper::new();
+        let mut buffers = crate::buffer::PortBuffers::new(&plugin, &mapper, 64);
+
+        let instance = unsafe { plugin.instantiate(44100.0, []) }.unwrap();
+        let symbols = vec!["gain".to_string(), "in".to_string(), "out".to_string()];
+        assert_eq!(
+            unsafe { instance.try_activate() }.err(),
+            Some(crate::Error::UnconnectedPorts(symbols))
+        );
+
+        let mut instance = unsafe { plugin.instantiate(44100.0, []) }.unwrap();
+        unsafe { buffers.connect(&mut instance) };
+        assert!(instance.is_connected(0));
+        let mut active_instance = unsafe { instance.try_activate() }.unwrap();
+        assert_eq!(unsafe { active_instance.try_run(64) }, Ok(()));
+    }
 }
@@ -1,15 +1,58 @@
+/// Contains functionality for reading and writing atoms and event sequences.
+pub mod atom;
+/// Contains typed buffers for connecting plugin ports.
+pub mod buffer;
+/// Contains an on-disk cache of plugin descriptions.
+#[cfg(feature = "catalogue")]
+pub mod catalogue;
+/// Contains functionality for providing features to plugin instances.
+pub mod feature;
+/// Contains a graph that runs several connected plugins.
+pub mod graph;
+/// Contains functionality for port groups and channel layouts.
+pub mod group;
+/// Contains owned descriptions of plugins that do not reference the world.
+pub mod info;
 /// Contains functionality for plugin instances that process data.
 pub mod instance;
+/// Contains a `log:log` feature that forwards plugin messages to Rust.
+pub mod logging;
+/// Contains helpers for sending MIDI to plugins.
+pub mod midi;
 /// Contains functionality for nodes. Nodes are used to represent metadata.
 pub mod node;
+/// Contains functionality for passing options to plugin instances.
+pub mod options;
+/// Contains functionality for discovering the parameters of plugins.
+pub mod parameter;
+/// Contains functionality for building and parsing patch messages.
+pub mod patch;
 /// Contains functionality to describe and instantiate plugins.
 pub mod plugin;
 /// Contains port to describe IO for plugins.
 pub mod port;
+/// Contains functionality for discovering and loading plugin presets.
+pub mod preset;
+/// Contains a handle for running plugin instances on realtime threads.
+pub mod processor;
+/// Contains an offline renderer that runs plugins over buffers of audio.
+pub mod render;
+/// Contains functionality to save and restore the state of plugin instances.
+pub mod state;
+/// Contains a transport that sends the host's time position to plugins.
+pub mod transport;
 /// Contains data about plugin UIs.
 pub mod ui;
+/// Contains functionality for mapping URIs to integers.
+pub mod urid;
+/// Contains nodes for well known URIs.
+pub mod uris;
+/// Contains a host implementation of the LV2 worker extension.
+pub mod worker;
 
+mod error;
 mod world;
 
+pub use error::Error;
 pub use lilv_sys as sys;
 pub use world::World;
@@ -0,0 +1,42 @@
+// Variadic callbacks for the LV2 log feature. Rust can not define C variadic
+// functions so the messages are formatted here and passed to the Rust sink.
+
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef void (*LilvRsLogMessage)(void* handle, uint32_t type, const char* message);
+
+// The first field of the Rust `LogShared` struct.
+typedef struct {
+    LilvRsLogMessage message;
+} LilvRsLogHandle;
+
+int lilv_rs_log_vprintf(void* handle, uint32_t type, const char* fmt, va_list ap) {
+    LilvRsLogHandle* log = (LilvRsLogHandle*)handle;
+    char buffer[512];
+    va_list ap_copy;
+    va_copy(ap_copy, ap);
+    int len = vsnprintf(buffer, sizeof(buffer), fmt, ap);
+    if (len >= 0 && (size_t)len < sizeof(buffer)) {
+        log->message(handle, type, buffer);
+    } else if (len >= 0) {
+        char* message = (char*)malloc((size_t)len + 1);
+        if (message) {
+            vsnprintf(message, (size_t)len + 1, fmt, ap_copy);
+            log->message(handle, type, message);
+            free(message);
+        }
+    }
+    va_end(ap_copy);
+    return len;
+}
+
+int lilv_rs_log_printf(void* handle, uint32_t type, const char* fmt, ...) {
+    va_list ap;
+    va_start(ap, fmt);
+    int len = lilv_rs_log_vprintf(handle, type, fmt, ap);
+    va_end(ap);
+    return len;
+}
@@ -0,0 +1,245 @@
+use crate::urid::UridMapper;
+use lv2_raw::{LV2Feature, LV2Urid};
+use std::ffi::CStr;
+use std::fmt::Debug;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::Arc;
+
+/// The URI for the `log:log` feature.
+pub const LOG_URI: &str = "http://lv2plug.in/ns/ext/log#log";
+
+const LOG_URI_C: &[u8] = b"http://lv2plug.in/ns/ext/log#log\0";
+const LOG_ERROR_URI: &str = "http://lv2plug.in/ns/ext/log#Error";
+const LOG_WARNING_URI: &str = "http://lv2plug.in/ns/ext/log#Warning";
+const LOG_NOTE_URI: &str = "http://lv2plug.in/ns/ext/log#Note";
+const LOG_TRACE_URI: &str = "http://lv2plug.in/ns/ext/log#Trace";
+
+/// Opaque handle for `LV2LogLog`.
+pub type LV2LogHandle = *mut c_void;
+
+/// The `log:log` feature provided by the host.
+#[repr(C)]
+pub struct LV2LogLog {
+    /// Opaque host data.
+    pub handle: LV2LogHandle,
+
+    /// Log a message with a printf-style format string.
+    pub printf: unsafe extern "C" fn(
+        handle: LV2LogHandle,
+        log_type: LV2Urid,
+        fmt: *const c_char,
+        ...
+    ) -> c_int,
+
+    /// Log a message with a printf-style format string and a `va_list`. The
+    /// `va_list` is opaque to Rust.
+    pub vprintf: unsafe extern "C" fn(
+        handle: LV2LogHandle,
+        log_type: LV2Urid,
+        fmt: *const c_char,
+        ap: *mut c_void,
+    ) -> c_int,
+}
+
+extern "C" {
+    // Defined in logging.c.
+    fn lilv_rs_log_printf(
+        handle: LV2LogHandle,
+        log_type: LV2Urid,
+        fmt: *const c_char,
+        ...
+    ) -> c_int;
+    fn lilv_rs_log_vprintf(
+        handle: LV2LogHandle,
+        log_type: LV2Urid,
+        fmt: *const c_char,
+        ap: *mut c_void,
+    ) -> c_int;
+}
+
+/// The level of a log message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    /// An error message, `log:Error`.
+    Error,
+    /// A warning message, `log:Warning`.
+    Warning,
+    /// An informative message, `log:Note`.
+    Note,
+    /// A debugging trace message, `log:Trace`.
+    Trace,
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> log::Level {
+        match level {
+            LogLevel::Error => log::Level::Error,
+            LogLevel::Warning => log::Level::Warn,
+            LogLevel::Note => log::Level::Info,
+            LogLevel::Trace => log::Level::Trace,
+        }
+    }
+}
+
+type LogSink = dyn Fn(LogLevel, &str) + Send + Sync;
+
+/// A host implementation of the `log:log` feature.
+///
+/// Messages from the plugin are formatted and passed to a sink. By default,
+/// messages are forwarded to the `log` crate with the `lilv::plugin` target.
+///
+/// # Example
+/// ```
+/// use lilv::feature::FeatureSet;
+/// use lilv::logging::Logger;
+/// use lilv::urid::UridMapper;
+///
+/// let mapper = UridMapper::new();
+/// let logger = Logger::with_sink(&mapper, |level, message| {
+///     eprintln!("{:?}: {}", level, message);
+/// });
+/// let features = FeatureSet::new()
+///     .with_urid_mapper(&mapper)
+///     .with_logger(&logger);
+/// assert!(features.contains(lilv::logging::LOG_URI));
+/// ```
+#[derive(Clone)]
+pub struct Logger {
+    shared: Arc<LogShared>,
+}
+
+// `message` must remain the first field, the C callbacks depend on it.
+#[repr(C)]
+struct LogShared {
+    message: extern "C" fn(handle: LV2LogHandle, log_type: LV2Urid, message: *const c_char),
+    log: LV2LogLog,
+    feature: LV2Feature,
+    levels: [(LV2Urid, LogLevel); 4],
+    sink: Box<LogSink>,
+}
+
+// The raw pointers within `LogShared` point to data owned by `LogShared` and
+// the sink is `Send` and `Sync`.
+unsafe impl Send for LogShared {}
+unsafe impl Sync for LogShared {}
+
+impl Logger {
+    /// Create a logger that forwards messages to the `log` crate. `mapper` is
+    /// used to map the log levels and must be the mapper that is passed to the
+    /// plugin.
+    #[must_use]
+    pub fn new(mapper: &UridMapper) -> Logger {
+        Logger::with_sink(mapper, |level, message| {
+            log::log!(target: "lilv::plugin", level.into(), "{}", message);
+        })
+    }
+
+    /// Create a logger that passes all messages to `sink`. Trailing newlines
+    /// are removed from the messages.
+    #[must_use]
+    pub fn with_sink<F>(mapper: &UridMapper, sink: F) -> Logger
+    where
+        F: 'static + Fn(LogLevel, &str) + Send + Sync,
+    {
+        let mut shared = Arc::new(LogShared {
+            message: log_message,
+            log: LV2LogLog {
+                handle: std::ptr::null_mut(),
+                printf: lilv_rs_log_printf,
+                vprintf: lilv_rs_log_vprintf,
+            },
+            feature: LV2Feature {
+                uri: LOG_URI_C.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            levels: [
+                (mapper.map(LOG_ERROR_URI), LogLevel::Error),
+                (mapper.map(LOG_WARNING_URI), LogLevel::Warning),
+                (mapper.map(LOG_NOTE_URI), LogLevel::Note),
+                (mapper.map(LOG_TRACE_URI), LogLevel::Trace),
+            ],
+            sink: Box::new(sink),
+        });
+        let handle = Arc::as_ptr(&shared) as *mut c_void;
+        // `shared` has not been shared yet so it is always unique.
+        if let Some(shared_mut) = Arc::get_mut(&mut shared) {
+            shared_mut.log.handle = handle;
+            shared_mut.feature.data = (&mut shared_mut.log as *mut LV2LogLog).cast();
+        }
+        Logger { shared }
+    }
+
+    /// The `log:log` feature.
+    #[must_use]
+    pub fn feature(&self) -> &LV2Feature {
+        &self.shared.feature
+    }
+
+    /// The level for the log type `urid`. Unknown types are treated as
+    /// `LogLevel::Note`.
+    #[must_use]
+    pub fn level(&self, urid: LV2Urid) -> LogLevel {
+        self.shared.level(urid)
+    }
+
+    /// Pass `message` to the sink.
+    pub fn log(&self, level: LogLevel, message: &str) {
+        (self.shared.sink)(level, message.trim_end_matches('\n'));
+    }
+}
+
+impl LogShared {
+    fn level(&self, urid: LV2Urid) -> LogLevel {
+        self.levels
+            .iter()
+            .find(|(u, _)| *u == urid)
+            .map_or(LogLevel::Note, |(_, level)| *level)
+    }
+}
+
+impl Debug for Logger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Logger").finish()
+    }
+}
+
+extern "C" fn log_message(handle: LV2LogHandle, log_type: LV2Urid, message: *const c_char) {
+    if handle.is_null() || message.is_null() {
+        return;
+    }
+    let shared = unsafe { &*handle.cast::<LogShared>() };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    (shared.sink)(shared.level(log_type), message.trim_end_matches('\n'));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_printf() {
+        let mapper = UridMapper::new();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let logger = {
+            let messages = messages.clone();
+            Logger::with_sink(&mapper, move |level, message| {
+                messages.lock().push((level, message.to_string()));
+            })
+        };
+        let log = unsafe { &*logger.feature().data.cast::<LV2LogLog>() };
+        unsafe {
+            (log.printf)(
+                log.handle,
+                mapper.map(LOG_WARNING_URI),
+                b"%s %d\n\0".as_ptr().cast(),
+                b"value\0".as_ptr(),
+                42 as c_int,
+            );
+        }
+        assert_eq!(
+            messages.lock().as_slice(),
+            &[(LogLevel::Warning, "value 42".to_string())]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::buffer::PortBuffers;
+use crate::plugin::Plugin;
+use crate::port::{Port, PortDirection, PortKind};
+use crate::world::World;
+
+impl Plugin {
+    /// Get the input port that receives MIDI events.
+    ///
+    /// The port designated as `lv2:control` is preferred if it supports
+    /// `midi:MidiEvent`. Otherwise, the first atom input port that supports
+    /// `midi:MidiEvent` is returned.
+    #[must_use]
+    pub fn midi_input_port(&self) -> Option<Port> {
+        let world = World::from_life(self.life.clone());
+        let uris = world.uris();
+        let midi_event = &uris.midi_midi_event;
+        let designated = self
+            .port_by_designation(Some(&uris.lv2_input_port), &uris.lv2_control)
+            .filter(|port| port.kind() == PortKind::Atom && port.supports_event(midi_event));
+        designated.or_else(|| {
+            self.iter_ports().find(|port| {
+                port.direction() == PortDirection::Input
+                    && port.kind() == PortKind::Atom
+                    && port.supports_event(midi_event)
+            })
+        })
+    }
+}
+
+/// Writes MIDI messages into the MIDI input port of a plugin.
+///
+/// # Example
+/// ```no_run
+/// use lilv::buffer::PortBuffers;
+/// use lilv::midi::MidiInput;
+/// use lilv::urid::UridMapper;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world.plugins().iter().next().unwrap();
+/// let mapper = UridMapper::new();
+/// let mut buffers = PortBuffers::new(&plugin, &mapper, 64);
+/// let midi = MidiInput::new(&plugin).unwrap();
+/// // Play middle C at the start of the block and release it at frame 32.
+/// midi.write(&mut buffers, &[(0, &[0x90, 60, 100][..]), (32, &[0x80, 60, 0][..])]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MidiInput {
+    index: usize,
+    symbol: String,
+}
+
+impl MidiInput {
+    /// Create a MIDI input for the port returned by `Plugin::midi_input_port`.
+    /// Returns `None` if the plugin has no MIDI input.
+    #[must_use]
+    pub fn new(plugin: &Plugin) -> Option<MidiInput> {
+        let port = plugin.midi_input_port()?;
+        Some(MidiInput {
+            index: port.index(),
+            symbol: port.symbol()?.as_str()?.to_string(),
+        })
+    }
+
+    /// The index of the MIDI input port.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The symbol of the MIDI input port.
+    #[must_use]
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Replace the contents of the MIDI input port in `buffers` with `events`
+    /// for the next block. Each event is a raw MIDI message and the frame
+    /// within the block at which it occurs. `events` must be sorted by frame.
+    ///
+    /// Returns the number of events that were written. This is less than the
+    /// number of events if the buffer is full or the events are not sorted.
+    pub fn write(&self, buffers: &mut PortBuffers, events: &[(i64, &[u8])]) -> usize {
+        match buffers.sequence_writer(&self.symbol) {
+            Some(mut writer) => events
+                .iter()
+                .take_while(|(frame, data)| writer.write_midi(*frame, data))
+                .count(),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amp_has_no_midi_input() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        assert!(plugin.midi_input_port().is_none());
+        assert!(MidiInput::new(&plugin).is_none());
+    }
+
+    #[test]
+    fn test_fifths_midi_input() {
+        use crate::atom::AtomValue;
+        use crate::urid::UridMapper;
+
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-fifths"))
+            .unwrap();
+        let midi = MidiInput::new(&plugin).unwrap();
+        let port = plugin.port_by_index(midi.index()).unwrap();
+        assert_eq!(port.direction(), PortDirection::Input);
+        assert_eq!(port.kind(), PortKind::Atom);
+
+        let mapper = UridMapper::new();
+        let mut buffers = PortBuffers::new(&plugin, &mapper, 64);
+        let note_on: &[u8] = &[0x90, 60, 100];
+        let note_off: &[u8] = &[0x80, 60, 0];
+        assert_eq!(midi.write(&mut buffers, &[(0, note_on), (32, note_off)]), 2);
+
+        let urids = *buffers.atom_urids();
+        let events: Vec<_> = buffers
+            .sequence(midi.symbol())
+            .unwrap()
+            .map(|event| (event.frame, event.atom.value(&urids)))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (0, AtomValue::Midi(note_on)),
+                (32, AtomValue::Midi(note_off))
+            ]
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use crate::error::Error;
+use crate::instance::Instance;
+use crate::urid::UridMapper;
+use lv2_raw::{LV2Handle, LV2Urid};
+use std::convert::TryInto;
+use std::os::raw::c_void;
+use std::sync::Arc;
+
+/// The URI for the `opts:options` feature.
+pub const OPTIONS_URI: &str = "http://lv2plug.in/ns/ext/options#options";
+/// The URI for the `opts:interface` extension data.
+pub const OPTIONS_INTERFACE_URI: &str = "http://lv2plug.in/ns/ext/options#interface";
+/// The URI for the `bufsz:boundedBlockLength` feature.
+pub const BOUNDED_BLOCK_LENGTH_URI: &str = "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength";
+/// The URI for the `bufsz:fixedBlockLength` feature.
+pub const FIXED_BLOCK_LENGTH_URI: &str = "http://lv2plug.in/ns/ext/buf-size#fixedBlockLength";
+/// The URI for the `bufsz:powerOf2BlockLength` feature.
+pub const POWER_OF_2_BLOCK_LENGTH_URI: &str =
+    "http://lv2plug.in/ns/ext/buf-size#powerOf2BlockLength";
+/// The URI for the `bufsz:minBlockLength` option.
+pub const MIN_BLOCK_LENGTH_URI: &str = "http://lv2plug.in/ns/ext/buf-size#minBlockLength";
+/// The URI for the `bufsz:maxBlockLength` option.
+pub const MAX_BLOCK_LENGTH_URI: &str = "http://lv2plug.in/ns/ext/buf-size#maxBlockLength";
+/// The URI for the `bufsz:nominalBlockLength` option.
+pub const NOMINAL_BLOCK_LENGTH_URI: &str = "http://lv2plug.in/ns/ext/buf-size#nominalBlockLength";
+/// The URI for the `bufsz:sequenceSize` option.
+pub const SEQUENCE_SIZE_URI: &str = "http://lv2plug.in/ns/ext/buf-size#sequenceSize";
+/// The URI for the `param:sampleRate` option.
+pub const SAMPLE_RATE_URI: &str = "http://lv2plug.in/ns/ext/parameters#sampleRate";
+/// The URI for the `ui:updateRate` option.
+pub const UI_UPDATE_RATE_URI: &str = "http://lv2plug.in/ns/extensions/ui#updateRate";
+
+const ATOM_INT_URI: &str = "http://lv2plug.in/ns/ext/atom#Int";
+const ATOM_FLOAT_URI: &str = "http://lv2plug.in/ns/ext/atom#Float";
+
+/// The context of an option, which specifies the subject it applies to.
+pub type LV2OptionsContext = u32;
+/// This option applies to the instance itself.
+pub const LV2_OPTIONS_INSTANCE: LV2OptionsContext = 0;
+/// This option applies to some named resource.
+pub const LV2_OPTIONS_RESOURCE: LV2OptionsContext = 1;
+/// This option applies to some blank node.
+pub const LV2_OPTIONS_BLANK: LV2OptionsContext = 2;
+/// This option applies to a port on the instance.
+pub const LV2_OPTIONS_PORT: LV2OptionsContext = 3;
+
+/// The status of an options operation. This is a bitmask of the error flags.
+pub type LV2OptionsStatus = u32;
+/// Completed successfully.
+pub const LV2_OPTIONS_SUCCESS: LV2OptionsStatus = 0;
+/// Unknown error.
+pub const LV2_OPTIONS_ERR_UNKNOWN: LV2OptionsStatus = 1;
+/// Invalid/unsupported subject.
+pub const LV2_OPTIONS_ERR_BAD_SUBJECT: LV2OptionsStatus = 1 << 1;
+/// Invalid/unsupported key.
+pub const LV2_OPTIONS_ERR_BAD_KEY: LV2OptionsStatus = 1 << 2;
+/// Invalid/unsupported value.
+pub const LV2_OPTIONS_ERR_BAD_VALUE: LV2OptionsStatus = 1 << 3;
+
+/// The errors that a plugin reported for an options operation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OptionsStatus {
+    /// `LV2_OPTIONS_ERR_UNKNOWN`, an unknown error.
+    pub unknown: bool,
+    /// `LV2_OPTIONS_ERR_BAD_SUBJECT`, the subject is invalid or unsupported.
+    pub bad_subject: bool,
+    /// `LV2_OPTIONS_ERR_BAD_KEY`, the key is invalid or unsupported.
+    pub bad_key: bool,
+    /// `LV2_OPTIONS_ERR_BAD_VALUE`, the value is invalid or unsupported.
+    pub bad_value: bool,
+}
+
+impl OptionsStatus {
+    /// Decode the bitmask of an `LV2OptionsStatus`. Unknown bits are reported
+    /// as an unknown error.
+    #[must_use]
+    pub fn from_raw(status: LV2OptionsStatus) -> OptionsStatus {
+        let known =
+            LV2_OPTIONS_ERR_BAD_SUBJECT | LV2_OPTIONS_ERR_BAD_KEY | LV2_OPTIONS_ERR_BAD_VALUE;
+        OptionsStatus {
+            unknown: status & !known != 0,
+            bad_subject: status & LV2_OPTIONS_ERR_BAD_SUBJECT != 0,
+            bad_key: status & LV2_OPTIONS_ERR_BAD_KEY != 0,
+            bad_value: status & LV2_OPTIONS_ERR_BAD_VALUE != 0,
+        }
+    }
+
+    /// Encode the status as an `LV2OptionsStatus` bitmask.
+    #[must_use]
+    pub fn to_raw(self) -> LV2OptionsStatus {
+        let mut status = LV2_OPTIONS_SUCCESS;
+        for (flag, bit) in [
+            (self.unknown, LV2_OPTIONS_ERR_UNKNOWN),
+            (self.bad_subject, LV2_OPTIONS_ERR_BAD_SUBJECT),
+            (self.bad_key, LV2_OPTIONS_ERR_BAD_KEY),
+            (self.bad_value, LV2_OPTIONS_ERR_BAD_VALUE),
+        ] {
+            if flag {
+                status |= bit;
+            }
+        }
+        status
+    }
+}
+
+impl std::fmt::Display for OptionsStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<&str> = [
+            (self.unknown, "unknown error"),
+            (self.bad_subject, "bad subject"),
+            (self.bad_key, "bad key"),
+            (self.bad_value, "bad value"),
+        ]
+        .iter()
+        .filter(|(flag, _)| *flag)
+        .map(|(_, name)| *name)
+        .collect();
+        write!(f, "{}", errors.join(", "))
+    }
+}
+
+/// An option, which is a property with a subject, key and value.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct LV2OptionsOption {
+    /// The context, which specifies the type of `subject`.
+    pub context: LV2OptionsContext,
+    /// The subject of the option.
+    pub subject: u32,
+    /// The key of the option.
+    pub key: LV2Urid,
+    /// The size of the value in bytes.
+    pub size: u32,
+    /// The type of the value.
+    pub value_type: LV2Urid,
+    /// A pointer to the value.
+    pub value: *const c_void,
+}
+
+/// The `opts:interface` extension data provided by plugins.
+#[repr(C)]
+pub struct LV2OptionsInterface {
+    /// Get the given options. Each element of `options` must have its
+    /// `context`, `subject` and `key` set and the plugin fills in the rest.
+    pub get:
+        Option<unsafe extern "C" fn(instance: LV2Handle, options: *mut LV2OptionsOption) -> u32>,
+    /// Set the given options.
+    pub set:
+        Option<unsafe extern "C" fn(instance: LV2Handle, options: *const LV2OptionsOption) -> u32>,
+}
+
+/// The value of an option.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionValue {
+    /// An `atom:Int`.
+    Int(i32),
+    /// An `atom:Float`.
+    Float(f32),
+    /// A value of any other type.
+    Other {
+        /// The URID of the type of the value.
+        value_type: LV2Urid,
+        /// The raw bytes of the value.
+        body: Vec<u8>,
+    },
+}
+
+/// A builder for the `opts:options` feature.
+///
+/// # Example
+/// ```
+/// use lilv::feature::FeatureSet;
+/// use lilv::options::Options;
+/// use lilv::urid::UridMapper;
+///
+/// let mapper = UridMapper::new();
+/// let options = Options::new(&mapper)
+///     .with_min_block_length(1)
+///     .with_max_block_length(512)
+///     .with_sample_rate(44100.0);
+/// let features = FeatureSet::new()
+///     .with_urid_mapper(&mapper)
+///     .with_options(&options);
+/// assert!(features.contains(lilv::options::OPTIONS_URI));
+/// assert!(features.contains(lilv::options::BOUNDED_BLOCK_LENGTH_URI));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Options {
+    mapper: UridMapper,
+    int_urid: LV2Urid,
+    float_urid: LV2Urid,
+    entries: Vec<(LV2Urid, OptionValue)>,
+}
+
+impl Options {
+    /// Create an empty set of options. `mapper` is used to map the keys and
+    /// types of the options and should be the mapper that is passed to the
+    /// plugin.
+    #[must_use]
+    pub fn new(mapper: &UridMapper) -> Options {
+        Options {
+            mapper: mapper.clone(),
+            int_urid: mapper.map(ATOM_INT_URI),
+            float_urid: mapper.map(ATOM_FLOAT_URI),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the `bufsz:minBlockLength` option.
+    #[must_use]
+    pub fn with_min_block_length(self, frames: i32) -> Options {
+        self.with_value(MIN_BLOCK_LENGTH_URI, OptionValue::Int(frames))
+    }
+
+    /// Set the `bufsz:maxBlockLength` option.
+    #[must_use]
+    pub fn with_max_block_length(self, frames: i32) -> Options {
+        self.with_value(MAX_BLOCK_LENGTH_URI, OptionValue::Int(frames))
+    }
+
+    /// Set the `bufsz:nominalBlockLength` option.
+    #[must_use]
+    pub fn with_nominal_block_length(self, frames: i32) -> Options {
+        self.with_value(NOMINAL_BLOCK_LENGTH_URI, OptionValue::Int(frames))
+    }
+
+    /// Set the `bufsz:sequenceSize` option, the size of atom sequence port
+    /// buffers in bytes.
+    #[must_use]
+    pub fn with_sequence_size(self, bytes: i32) -> Options {
+        self.with_value(SEQUENCE_SIZE_URI, OptionValue::Int(bytes))
+    }
+
+    /// Set the `param:sampleRate` option.
+    #[must_use]
+    pub fn with_sample_rate(self, sample_rate: f32) -> Options {
+        self.with_value(SAMPLE_RATE_URI, OptionValue::Float(sample_rate))
+    }
+
+    /// Set the `ui:updateRate` option, the rate at which the UI is updated in
+    /// Hz.
+    #[must_use]
+    pub fn with_ui_update_rate(self, rate: f32) -> Options {
+        self.with_value(UI_UPDATE_RATE_URI, OptionValue::Float(rate))
+    }
+
+    /// Set the option with the key `key_uri` to `value`. If the option already
+    /// exists, then it is replaced.
+    #[must_use]
+    pub fn with_value(self, key_uri: &str, value: OptionValue) -> Options {
+        let mut options = self;
+        let key = options.mapper.map(key_uri);
+        options.entries.retain(|(k, _)| *k != key);
+        options.entries.push((key, value));
+        options
+    }
+
+    /// Get the value of the option with the key `key_uri`.
+    #[must_use]
+    pub fn get(&self, key_uri: &str) -> Option<&OptionValue> {
+        let key = self.mapper.map(key_uri);
+        self.entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// The number of options.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no options.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if both the minimum and maximum block length are set.
+    /// This is what the `bufsz:boundedBlockLength` feature promises.
+    #[must_use]
+    pub fn is_bounded(&self) -> bool {
+        self.get(MIN_BLOCK_LENGTH_URI).is_some() && self.get(MAX_BLOCK_LENGTH_URI).is_some()
+    }
+
+    /// Build the null terminated array of options. The returned data owns the
+    /// values that the options point to.
+    pub(crate) fn feature_data(&self) -> Arc<OptionsData> {
+        let values: Vec<Box<[u64]>> = self
+            .entries
+            .iter()
+            .map(|(_, value)| {
+                let bytes = self.value_bytes(value);
+                let mut words = vec![0u64; bytes.len().div_ceil(8)].into_boxed_slice();
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        bytes.as_ptr(),
+                        words.as_mut_ptr().cast::<u8>(),
+                        bytes.len(),
+                    );
+                }
+                words
+            })
+            .collect();
+        #[allow(clippy::cast_possible_truncation)]
+        let options = self
+            .entries
+            .iter()
+            .zip(&values)
+            .map(|((key, value), words)| LV2OptionsOption {
+                context: LV2_OPTIONS_INSTANCE,
+                subject: 0,
+                key: *key,
+                size: self.value_bytes(value).len() as u32,
+                value_type: self.value_type(value),
+                value: words.as_ptr().cast(),
+            })
+            .chain(std::iter::once(null_option()))
+            .collect();
+        Arc::new(OptionsData {
+            options,
+            _values: values,
+        })
+    }
+
+    fn value_type(&self, value: &OptionValue) -> LV2Urid {
+        match value {
+            OptionValue::Int(_) => self.int_urid,
+            OptionValue::Float(_) => self.float_urid,
+            OptionValue::Other { value_type, .. } => *value_type,
+        }
+    }
+
+    fn value_bytes(&self, value: &OptionValue) -> Vec<u8> {
+        match value {
+            OptionValue::Int(v) => v.to_ne_bytes().to_vec(),
+            OptionValue::Float(v) => v.to_ne_bytes().to_vec(),
+            OptionValue::Other { body, .. } => body.clone(),
+        }
+    }
+
+    fn decode(&self, option: &LV2OptionsOption) -> Option<OptionValue> {
+        if option.value.is_null() {
+            return None;
+        }
+        let body =
+            unsafe { std::slice::from_raw_parts(option.value.cast::<u8>(), option.size as usize) };
+        let bytes4 = || -> Option<[u8; 4]> { body.get(..4)?.try_into().ok() };
+        if option.value_type == self.int_urid {
+            bytes4().map(|b| OptionValue::Int(i32::from_ne_bytes(b)))
+        } else if option.value_type == self.float_urid {
+            bytes4().map(|b| OptionValue::Float(f32::from_ne_bytes(b)))
+        } else {
+            Some(OptionValue::Other {
+                value_type: option.value_type,
+                body: body.to_vec(),
+            })
+        }
+    }
+}
+
+/// The data for the `opts:options` feature.
+pub(crate) struct OptionsData {
+    options: Vec<LV2OptionsOption>,
+    // The values that `options` point to.
+    _values: Vec<Box<[u64]>>,
+}
+
+// The options only point to the values owned by `OptionsData`, which are
+// never modified.
+unsafe impl Send for OptionsData {}
+unsafe impl Sync for OptionsData {}
+
+impl OptionsData {
+    /// A pointer to the null terminated array of options.
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self.options.as_ptr() as *mut c_void
+    }
+}
+
+fn null_option() -> LV2OptionsOption {
+    LV2OptionsOption {
+        context: LV2_OPTIONS_INSTANCE,
+        subject: 0,
+        key: 0,
+        size: 0,
+        value_type: 0,
+        value: std::ptr::null(),
+    }
+}
+
+/// The `opts:interface` of a plugin instance, used to get and set options
+/// after instantiation.
+pub struct OptionsInterface<'a> {
+    instance: &'a Instance,
+    interface: &'a LV2OptionsInterface,
+}
+
+impl Instance {
+    /// Get the `opts:interface` extension of the instance, or `None` if the
+    /// plugin does not provide it.
+    ///
+    /// # Safety
+    /// Gathering extension data calls a plugin's code, which itself may be
+    /// unsafe.
+    #[must_use]
+    pub unsafe fn options_interface(&self) -> Option<OptionsInterface<'_>> {
+        let interface = self.extension_data::<LV2OptionsInterface>(OPTIONS_INTERFACE_URI)?;
+        Some(OptionsInterface {
+            instance: self,
+            interface: &*interface.as_ptr(),
+        })
+    }
+}
+
+impl OptionsInterface<'_> {
+    /// Get the value of the option with the key `key_uri` from the instance.
+    /// `options` is used to map and decode the option.
+    ///
+    /// # Errors
+    /// Returns `Error::Options` with the status that the plugin reported if
+    /// the option could not be read, or with `bad_value` set if the value
+    /// could not be decoded.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe.
+    pub unsafe fn get(&self, options: &Options, key_uri: &str) -> Result<OptionValue, Error> {
+        let get = self
+            .interface
+            .get
+            .ok_or_else(|| options_error(LV2_OPTIONS_ERR_UNKNOWN))?;
+        let mut query = [
+            LV2OptionsOption {
+                key: options.mapper.map(key_uri),
+                ..null_option()
+            },
+            null_option(),
+        ];
+        let status = get(self.instance.handle(), query.as_mut_ptr());
+        if status != LV2_OPTIONS_SUCCESS {
+            return Err(options_error(status));
+        }
+        options
+            .decode(&query[0])
+            .ok_or_else(|| options_error(LV2_OPTIONS_ERR_BAD_VALUE))
+    }
+
+    /// Set all the options in `options` on the instance.
+    ///
+    /// # Errors
+    /// Returns `Error::Options` with the status that the plugin reported if
+    /// the options could not be set.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe.
+    pub unsafe fn set(&self, options: &Options) -> Result<(), Error> {
+        let set = self
+            .interface
+            .set
+            .ok_or_else(|| options_error(LV2_OPTIONS_ERR_UNKNOWN))?;
+        let data = options.feature_data();
+        match set(self.instance.handle(), data.options.as_ptr()) {
+            LV2_OPTIONS_SUCCESS => Ok(()),
+            status => Err(options_error(status)),
+        }
+    }
+}
+
+fn options_error(status: LV2OptionsStatus) -> Error {
+    Error::Options(OptionsStatus::from_raw(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature::FeatureSet;
+    use crate::world::World;
+
+    #[test]
+    fn test_options_data() {
+        let mapper = UridMapper::new();
+        let options = Options::new(&mapper)
+            .with_max_block_length(256)
+            .with_sample_rate(48000.0)
+            .with_max_block_length(512);
+        assert_eq!(options.len(), 2);
+        assert!(!options.is_bounded());
+
+        let data = options.feature_data();
+        assert_eq!(data.options.len(), 3);
+        assert_eq!(
+            options.decode(&data.options[0]),
+            Some(OptionValue::Float(48000.0))
+        );
+        assert_eq!(data.options[1].key, mapper.map(MAX_BLOCK_LENGTH_URI));
+        assert_eq!(
+            options.decode(&data.options[1]),
+            Some(OptionValue::Int(512))
+        );
+        assert_eq!(data.options[2].key, 0);
+        assert!(data.options[2].value.is_null());
+    }
+
+    #[test]
+    fn test_options_interface() {
+        let world = World::with_test_bundle();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://example.org/lilv-test"))
+            .unwrap();
+        let mapper = UridMapper::new();
+        let options = Options::new(&mapper).with_nominal_block_length(64);
+        let features = FeatureSet::new()
+            .with_urid_mapper(&mapper)
+            .with_options(&options);
+        let instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+        let interface = unsafe { instance.options_interface() }.unwrap();
+        let get = |key_uri| unsafe { interface.get(&options, key_uri) };
+        let set = |options: Options| unsafe { interface.set(&options) };
+        assert_eq!(get(NOMINAL_BLOCK_LENGTH_URI), Ok(OptionValue::Int(64)));
+
+        set(Options::new(&mapper).with_nominal_block_length(128)).unwrap();
+        assert_eq!(get(NOMINAL_BLOCK_LENGTH_URI), Ok(OptionValue::Int(128)));
+
+        let bad_key = Error::Options(OptionsStatus {
+            bad_key: true,
+            ..OptionsStatus::default()
+        });
+        assert_eq!(get(MAX_BLOCK_LENGTH_URI), Err(bad_key.clone()));
+        assert_eq!(
+            set(Options::new(&mapper).with_sample_rate(48000.0)),
+            Err(bad_key)
+        );
+        let wrong_type =
+            Options::new(&mapper).with_value(NOMINAL_BLOCK_LENGTH_URI, OptionValue::Float(256.0));
+        assert_eq!(
+            set(wrong_type),
+            Err(Error::Options(OptionsStatus {
+                bad_value: true,
+                ..OptionsStatus::default()
+            }))
+        );
+        assert_eq!(get(NOMINAL_BLOCK_LENGTH_URI), Ok(OptionValue::Int(128)));
+    }
+
+    #[test]
+    fn test_options_status() {
+        let status = OptionsStatus::from_raw(LV2_OPTIONS_ERR_BAD_KEY | LV2_OPTIONS_ERR_BAD_VALUE);
+        assert!(status.bad_key && status.bad_value);
+        assert!(!status.unknown && !status.bad_subject);
+        assert_eq!(status.to_string(), "bad key, bad value");
+        assert_eq!(
+            status.to_raw(),
+            LV2_OPTIONS_ERR_BAD_KEY | LV2_OPTIONS_ERR_BAD_VALUE
+        );
+        assert!(OptionsStatus::from_raw(1 << 4).unknown);
+    }
+}
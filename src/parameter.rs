@@ -0,0 +1,169 @@
+use crate::node::Node;
+use crate::plugin::Plugin;
+use crate::port::Range;
+use crate::world::World;
+
+const MOD_FILE_TYPES: &str = "http://moddevices.com/ns/mod#fileTypes";
+const ATOM_PATH: &str = "http://lv2plug.in/ns/ext/atom#Path";
+
+/// A parameter of a plugin that is accessed with patch messages instead of a
+/// control port.
+#[derive(Clone, Debug)]
+pub struct Parameter {
+    /// The URI of the parameter. This is the key of the patch messages.
+    pub uri: Node,
+    /// The human readable label of the parameter.
+    pub label: Option<String>,
+    /// The range of the parameter.
+    pub range: Range,
+    /// The type of the value, for example `atom:Float` or `atom:Path`.
+    pub value_type: Option<Node>,
+    /// The units of the value.
+    pub units: Option<Node>,
+    /// The file types that are accepted by a path parameter, for example
+    /// `wav` or `flac`.
+    pub file_types: Vec<String>,
+    /// `true` if the parameter is `patch:writable` and can be set by the host.
+    pub writable: bool,
+    /// `true` if the parameter is `patch:readable` and is reported by the
+    /// plugin.
+    pub readable: bool,
+}
+
+impl Parameter {
+    /// Returns `true` if the value of the parameter is an `atom:Path`.
+    #[must_use]
+    pub fn is_path(&self) -> bool {
+        self.value_type.as_ref().and_then(Node::as_uri) == Some(ATOM_PATH)
+    }
+}
+
+impl Plugin {
+    /// Get all the parameters of the plugin. These are the `patch:writable` and
+    /// `patch:readable` properties of the plugin.
+    #[must_use]
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let world = World::from_life(self.life.clone());
+        let uris = world.uris();
+        let writable = self.value(&uris.patch_writable);
+        let readable = self.value(&uris.patch_readable);
+        let file_types_pred = world.new_uri(MOD_FILE_TYPES);
+
+        let mut keys: Vec<Node> = writable.iter().collect();
+        for uri in readable.iter() {
+            if !writable.contains(&uri) {
+                keys.push(uri);
+            }
+        }
+        keys.into_iter()
+            .map(|uri| {
+                let get = |pred: &Node| world.get(Some(&uri), Some(pred), None);
+                let file_types = world
+                    .find_nodes(Some(&uri), &file_types_pred, None)
+                    .iter()
+                    .filter_map(|n| n.as_str().map(str::to_string))
+                    .flat_map(|types| {
+                        types
+                            .split(',')
+                            .map(|t| t.trim().to_string())
+                            .filter(|t| !t.is_empty())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                Parameter {
+                    label: get(&uris.rdfs_label).and_then(|n| n.as_str().map(str::to_string)),
+                    range: Range {
+                        default: get(&uris.lv2_default),
+                        minimum: get(&uris.lv2_minimum),
+                        maximum: get(&uris.lv2_maximum),
+                    },
+                    value_type: get(&uris.rdfs_range),
+                    units: get(&uris.units_unit),
+                    file_types,
+                    writable: writable.contains(&uri),
+                    readable: readable.contains(&uri),
+                    uri,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amp_has_no_parameters() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        assert!(plugin.parameters().is_empty());
+    }
+
+    #[test]
+    fn test_sampler_parameters() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-sampler"))
+            .unwrap();
+        let parameters = plugin.parameters();
+        let sample = parameters
+            .iter()
+            .find(|p| p.uri.as_uri() == Some("http://lv2plug.in/plugins/eg-sampler#sample"))
+            .unwrap();
+        assert!(sample.writable);
+        assert!(sample.is_path());
+        assert!(sample.label.is_some());
+    }
+
+    #[test]
+    fn test_parameter_properties() {
+        let world = World::with_test_bundle();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://example.org/lilv-test"))
+            .unwrap();
+        let parameters = plugin.parameters();
+        assert_eq!(parameters.len(), 2);
+
+        let sample = parameters
+            .iter()
+            .find(|p| p.uri.as_uri() == Some("http://example.org/lilv-test#sample"))
+            .unwrap();
+        assert_eq!(sample.label.as_deref(), Some("Sample"));
+        assert!(sample.is_path());
+        assert_eq!(sample.file_types, vec!["wav", "flac"]);
+        assert!(sample.writable && !sample.readable);
+
+        let gain = parameters
+            .iter()
+            .find(|p| p.uri.as_uri() == Some("http://example.org/lilv-test#gain"))
+            .unwrap();
+        assert_eq!(gain.label.as_deref(), Some("Gain"));
+        assert_eq!(
+            gain.value_type.as_ref().and_then(Node::as_uri),
+            Some("http://lv2plug.in/ns/ext/atom#Float")
+        );
+        assert_eq!(
+            gain.range.default.as_ref().and_then(Node::as_float),
+            Some(0.0)
+        );
+        assert_eq!(
+            gain.range.minimum.as_ref().and_then(Node::as_float),
+            Some(-90.0)
+        );
+        assert_eq!(
+            gain.range.maximum.as_ref().and_then(Node::as_float),
+            Some(24.0)
+        );
+        assert_eq!(
+            gain.units.as_ref().and_then(Node::as_uri),
+            Some("http://lv2plug.in/ns/extensions/units#db")
+        );
+        assert!(gain.file_types.is_empty());
+    }
+}
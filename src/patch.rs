@@ -0,0 +1,208 @@
+use crate::atom::{Atom, AtomEvent, AtomUrids, AtomValue, SequenceWriter};
+use crate::plugin::Plugin;
+use crate::port::{Port, PortDirection, PortKind};
+use crate::urid::UridMapper;
+use crate::world::World;
+use lv2_raw::LV2Urid;
+
+/// The URI for `patch:Message`.
+pub const PATCH_MESSAGE_URI: &str = "http://lv2plug.in/ns/ext/patch#Message";
+
+const PATCH_GET: &str = "http://lv2plug.in/ns/ext/patch#Get";
+const PATCH_SET: &str = "http://lv2plug.in/ns/ext/patch#Set";
+const PATCH_PROPERTY: &str = "http://lv2plug.in/ns/ext/patch#property";
+const PATCH_VALUE: &str = "http://lv2plug.in/ns/ext/patch#value";
+
+impl Plugin {
+    /// Get the atom input port that receives patch messages.
+    #[must_use]
+    pub fn patch_input_port(&self) -> Option<Port> {
+        self.patch_port(PortDirection::Input)
+    }
+
+    /// Get the atom output port that sends patch messages.
+    #[must_use]
+    pub fn patch_output_port(&self) -> Option<Port> {
+        self.patch_port(PortDirection::Output)
+    }
+
+    fn patch_port(&self, direction: PortDirection) -> Option<Port> {
+        let world = World::from_life(self.life.clone());
+        let message = &world.uris().patch_message;
+        self.iter_ports().find(|port| {
+            port.direction() == direction
+                && port.kind() == PortKind::Atom
+                && port.supports_event(message)
+        })
+    }
+}
+
+/// A `patch:Set` message that was read from a sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchSet<'a> {
+    /// The time of the message in frames.
+    pub frame: i64,
+    /// The URI of the parameter that was set.
+    pub property: String,
+    /// The new value of the parameter.
+    pub value: Atom<'a>,
+}
+
+/// Builds and parses patch messages for controlling plugin parameters.
+///
+/// # Example
+/// ```no_run
+/// use lilv::atom::AtomValue;
+/// use lilv::buffer::PortBuffers;
+/// use lilv::patch::Patch;
+/// use lilv::urid::UridMapper;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world.plugins().iter().next().unwrap();
+/// let mapper = UridMapper::new();
+/// let patch = Patch::new(&mapper);
+/// let mut buffers = PortBuffers::new(&plugin, &mapper, 64);
+/// let input = plugin.patch_input_port().unwrap();
+/// let symbol = input.symbol().unwrap();
+/// let mut writer = buffers.sequence_writer(symbol.as_str().unwrap()).unwrap();
+/// patch.write_set(
+///     &mut writer,
+///     0,
+///     "http://example.org/sample",
+///     &AtomValue::Path("/tmp/kick.wav"),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Patch {
+    mapper: UridMapper,
+    atom: AtomUrids,
+    get: LV2Urid,
+    set: LV2Urid,
+    property: LV2Urid,
+    value: LV2Urid,
+}
+
+impl Patch {
+    /// Create a new patch helper. `mapper` must be the mapper that is passed
+    /// to the plugin.
+    #[must_use]
+    pub fn new(mapper: &UridMapper) -> Patch {
+        Patch {
+            mapper: mapper.clone(),
+            atom: AtomUrids::new(mapper),
+            get: mapper.map(PATCH_GET),
+            set: mapper.map(PATCH_SET),
+            property: mapper.map(PATCH_PROPERTY),
+            value: mapper.map(PATCH_VALUE),
+        }
+    }
+
+    /// The URIDs of the atom types, for reading the values of `PatchSet`.
+    #[must_use]
+    pub fn atom_urids(&self) -> &AtomUrids {
+        &self.atom
+    }
+
+    /// Write a `patch:Set` message that sets the parameter `property` to
+    /// `value`. Returns `false` if the message could not be written.
+    pub fn write_set(
+        &self,
+        writer: &mut SequenceWriter,
+        frame: i64,
+        property: &str,
+        value: &AtomValue,
+    ) -> bool {
+        writer.write_object(
+            frame,
+            0,
+            self.set,
+            &[
+                (self.property, AtomValue::Urid(self.mapper.map(property))),
+                (self.value, *value),
+            ],
+        )
+    }
+
+    /// Write a `patch:Get` message that requests the value of the parameter
+    /// `property`, or all parameters if `property` is `None`. The plugin
+    /// replies with `patch:Set` messages on its output port. Returns `false`
+    /// if the message could not be written.
+    pub fn write_get(
+        &self,
+        writer: &mut SequenceWriter,
+        frame: i64,
+        property: Option<&str>,
+    ) -> bool {
+        match property {
+            Some(property) => writer.write_object(
+                frame,
+                0,
+                self.get,
+                &[(self.property, AtomValue::Urid(self.mapper.map(property)))],
+            ),
+            None => writer.write_object(frame, 0, self.get, &[]),
+        }
+    }
+
+    /// Parse `event` as a `patch:Set` message. Returns `None` if the event is
+    /// not a valid `patch:Set` message.
+    #[must_use]
+    pub fn parse_set<'a>(&self, event: &AtomEvent<'a>) -> Option<PatchSet<'a>> {
+        let object = event.atom.object(&self.atom)?;
+        if object.otype != self.set {
+            return None;
+        }
+        let property = match object.get(self.property)?.value(&self.atom) {
+            AtomValue::Urid(urid) => self.mapper.unmap(urid)?.to_string(),
+            _ => return None,
+        };
+        Some(PatchSet {
+            frame: event.frame,
+            property,
+            value: object.get(self.value)?,
+        })
+    }
+
+    /// Get all the `patch:Set` messages within `events`, such as the events of
+    /// the plugin's patch output port after `run`.
+    pub fn read_sets<'a, I>(&'a self, events: I) -> impl 'a + Iterator<Item = PatchSet<'a>>
+    where
+        I: 'a + IntoIterator<Item = AtomEvent<'a>>,
+    {
+        events
+            .into_iter()
+            .filter_map(move |event| self.parse_set(&event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom::{SequenceBuffer, SequenceIter};
+
+    #[test]
+    fn test_set_round_trip() {
+        let mapper = UridMapper::new();
+        let patch = Patch::new(&mapper);
+        let mut buffer = SequenceBuffer::new(512);
+        let bytes = buffer.bytes_mut();
+        let mut writer = SequenceWriter::new(bytes, patch.atom_urids()).unwrap();
+        assert!(patch.write_get(&mut writer, 0, None));
+        assert!(patch.write_set(
+            &mut writer,
+            1,
+            "http://example.org/gain",
+            &AtomValue::Float(0.25)
+        ));
+
+        let events = SequenceIter::new(bytes, patch.atom_urids()).unwrap();
+        let sets: Vec<_> = patch.read_sets(events).collect();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].frame, 1);
+        assert_eq!(sets[0].property, "http://example.org/gain");
+        assert_eq!(
+            sets[0].value.value(patch.atom_urids()),
+            AtomValue::Float(0.25)
+        );
+    }
+}
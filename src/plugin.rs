@@ -1,4 +1,6 @@
-use crate::instance::Instance;
+use crate::error::Error;
+use crate::feature::FeatureSet;
+use crate::instance::{Instance, PortConnection};
 use crate::node::{Node, Nodes};
 use crate::port::{FloatRanges, Port};
 use crate::ui::Uis;
@@ -478,14 +480,31 @@ impl Plugin {
 
     /// Instantiate a plugin.
     ///
+    /// The instance does not keep the data of `features` alive. Use
+    /// `instantiate_with_features` or `try_instantiate` to keep a `FeatureSet`
+    /// alive with the instance.
+    ///
     /// # Safety
     /// Instantiating a plugin calls the plugin's code which itself may be
-    /// unsafe.
+    /// unsafe. The data of `features` must remain valid for as long as the
+    /// instance is alive.
     #[must_use]
     pub unsafe fn instantiate<'a, FS>(&self, sample_rate: f64, features: FS) -> Option<Instance>
     where
         FS: IntoIterator<Item = &'a LV2Feature>,
     {
+        let latency_port = self.latency_port_index();
+        let ports: Vec<PortConnection> = self
+            .iter_ports()
+            .map(|port| PortConnection {
+                symbol: port
+                    .symbol()
+                    .and_then(|s| s.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+                optional: port.flags().connection_optional,
+                data: std::ptr::null(),
+            })
+            .collect();
         let _life = self.life.inner.lock();
         let plugin = self.inner.as_ptr();
         let features_vec: Vec<*const LV2Feature> = features
@@ -499,7 +518,65 @@ impl Plugin {
             features_vec.as_ptr(),
         ))?;
 
-        Some(Instance { inner })
+        Some(Instance {
+            inner,
+            features: FeatureSet::default(),
+            latency_port,
+            latency: std::ptr::null(),
+            ports,
+            life: self.life.clone(),
+        })
+    }
+
+    /// Instantiate a plugin with the features in `features`.
+    ///
+    /// The returned instance keeps the feature data alive. Returns `None` if
+    /// the plugin could not be instantiated. Use
+    /// `FeatureSet::missing_required_features` to find out if the plugin
+    /// requires features that are not in the set.
+    ///
+    /// # Safety
+    /// Instantiating a plugin calls the plugin's code which itself may be
+    /// unsafe.
+    #[must_use]
+    pub unsafe fn instantiate_with_features(
+        &self,
+        sample_rate: f64,
+        features: &FeatureSet,
+    ) -> Option<Instance> {
+        let mut instance = self.instantiate(sample_rate, features.iter())?;
+        instance.features = features.clone();
+        Some(instance)
+    }
+
+    /// Instantiate a plugin with the features in `features`.
+    ///
+    /// This is similar to `instantiate_with_features` but reports why the
+    /// plugin could not be instantiated.
+    ///
+    /// # Errors
+    /// Returns `Error::MissingFeatures` if the plugin requires features that
+    /// are not in `features` and `Error::InstantiationFailed` if the plugin
+    /// failed to instantiate.
+    ///
+    /// # Safety
+    /// Instantiating a plugin calls the plugin's code which itself may be
+    /// unsafe.
+    pub unsafe fn try_instantiate(
+        &self,
+        sample_rate: f64,
+        features: &FeatureSet,
+    ) -> Result<Instance, Error> {
+        let uri = || self.uri().as_uri().unwrap_or_default().to_string();
+        let missing = features.missing_required_features(self);
+        if !missing.is_empty() {
+            return Err(Error::MissingFeatures {
+                plugin: uri(),
+                features: missing,
+            });
+        }
+        self.instantiate_with_features(sample_rate, features)
+            .ok_or_else(|| Error::InstantiationFailed(uri()))
     }
 }
 
@@ -184,6 +184,66 @@ impl Port {
         unsafe { lib::lilv_port_is_a(plugin, port, port_class) }
     }
 
+    /// Returns the direction of the port.
+    #[must_use]
+    pub fn direction(&self) -> PortDirection {
+        let uris = self.plugin.life.uri_table();
+        if self.is_a_raw(uris.lv2_input_port) {
+            PortDirection::Input
+        } else if self.is_a_raw(uris.lv2_output_port) {
+            PortDirection::Output
+        } else {
+            PortDirection::Unknown
+        }
+    }
+
+    /// Returns the kind of data that the port carries.
+    #[must_use]
+    pub fn kind(&self) -> PortKind {
+        let uris = self.plugin.life.uri_table();
+        if self.is_a_raw(uris.lv2_control_port) {
+            PortKind::Control
+        } else if self.is_a_raw(uris.lv2_audio_port) {
+            PortKind::Audio
+        } else if self.is_a_raw(uris.lv2_cv_port) {
+            PortKind::Cv
+        } else if self.is_a_raw(uris.atom_atom_port) {
+            PortKind::Atom
+        } else {
+            PortKind::Unknown
+        }
+    }
+
+    /// Returns the common properties of the port.
+    #[must_use]
+    pub fn flags(&self) -> PortFlags {
+        let uris = self.plugin.life.uri_table();
+        PortFlags {
+            toggled: self.has_property_raw(uris.lv2_toggled),
+            integer: self.has_property_raw(uris.lv2_integer),
+            enumeration: self.has_property_raw(uris.lv2_enumeration),
+            sample_rate: self.has_property_raw(uris.lv2_sample_rate),
+            logarithmic: self.has_property_raw(uris.pprops_logarithmic),
+            connection_optional: self.has_property_raw(uris.lv2_connection_optional),
+        }
+    }
+
+    fn is_a_raw(&self, port_class: NonNull<lib::LilvNodeImpl>) -> bool {
+        let _life = self.plugin.life.inner.lock();
+        let plugin = self.plugin.inner.as_ptr();
+        let port = self.inner.as_ptr();
+
+        unsafe { lib::lilv_port_is_a(plugin, port, port_class.as_ptr()) }
+    }
+
+    fn has_property_raw(&self, property: NonNull<lib::LilvNodeImpl>) -> bool {
+        let _life = self.plugin.life.inner.lock();
+        let plugin = self.plugin.inner.as_ptr();
+        let port = self.inner.as_ptr();
+
+        unsafe { lib::lilv_port_has_property(plugin, port, property.as_ptr()) }
+    }
+
     /// The the range (default, minimum, maximum) values of the port.
     ///
     /// # Panics
@@ -241,6 +301,52 @@ impl Port {
     }
 }
 
+/// The direction of a port.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PortDirection {
+    /// An `lv2:InputPort`.
+    Input,
+    /// An `lv2:OutputPort`.
+    Output,
+    /// A port that is neither an input nor an output.
+    Unknown,
+}
+
+/// The kind of data that a port carries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PortKind {
+    /// An `lv2:ControlPort` with a single `f32` value.
+    Control,
+    /// An `lv2:AudioPort` with a block of audio samples.
+    Audio,
+    /// An `lv2:CVPort` with a block of control voltage samples.
+    Cv,
+    /// An `atom:AtomPort`, usually with an `atom:Sequence` of events.
+    Atom,
+    /// A port with a type that is not known.
+    Unknown,
+}
+
+/// The common properties of a port.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortFlags {
+    /// `lv2:toggled`, the value is either on or off.
+    pub toggled: bool,
+    /// `lv2:integer`, the value is an integer.
+    pub integer: bool,
+    /// `lv2:enumeration`, the value is one of the scale points.
+    pub enumeration: bool,
+    /// `lv2:sampleRate`, the range is a fraction of the sample rate.
+    pub sample_rate: bool,
+    /// `pprops:logarithmic`, the value is best shown on a logarithmic scale.
+    pub logarithmic: bool,
+    /// `lv2:connectionOptional`, the port may be connected to null.
+    pub connection_optional: bool,
+}
+
 impl Debug for Port {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Port")
@@ -429,3 +535,33 @@ pub struct FloatRanges {
     /// The maximum value of the port.
     pub max: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_amp_port_kinds() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let kinds: Vec<_> = plugin
+            .iter_ports()
+            .map(|port| (port.kind(), port.direction()))
+            .collect();
+        assert_eq!(
+            kinds,
+            &[
+                (PortKind::Control, PortDirection::Input),
+                (PortKind::Audio, PortDirection::Input),
+                (PortKind::Audio, PortDirection::Output),
+            ]
+        );
+        for port in plugin.iter_ports() {
+            assert_eq!(port.flags(), PortFlags::default());
+        }
+    }
+}
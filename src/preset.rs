@@ -0,0 +1,161 @@
+use crate::error::Error;
+use crate::node::Node;
+use crate::plugin::Plugin;
+use crate::state::State;
+use crate::world::World;
+use lv2_raw::LV2UridMap;
+
+/// A preset for a plugin.
+#[derive(Clone, Debug)]
+pub struct Preset {
+    /// The URI of the preset.
+    pub uri: Node,
+    /// The human readable label of the preset.
+    pub label: Option<String>,
+    /// The bank the preset belongs to.
+    pub bank: Option<Node>,
+    /// The URI of the bundle that contains the preset data.
+    pub bundle: Option<Node>,
+}
+
+/// The data of a preset that has been loaded.
+#[derive(Debug)]
+pub struct LoadedPreset {
+    /// The values for the plugin's ports as `(symbol, value)` pairs.
+    pub port_values: Vec<(String, f32)>,
+    /// The full state of the preset. This can be applied to an instance with
+    /// `State::restore`.
+    pub state: State,
+}
+
+impl Preset {
+    /// Load the preset.
+    ///
+    /// `map` is used to map the URIs within the preset and must be the same map
+    /// that is passed to the instances the preset will be applied to.
+    ///
+    /// # Errors
+    /// Returns `Error::ParseFailure` if the preset could not be loaded.
+    pub fn load(&self, world: &World, map: &LV2UridMap) -> Result<LoadedPreset, Error> {
+        world.try_load_resource(&self.uri)?;
+        let state = State::new_from_world(world, map, &self.uri)
+            .ok_or_else(|| Error::ParseFailure(self.uri.turtle_token()))?;
+        let mut port_values = Vec::new();
+        state.emit_port_values(map, |symbol, value| {
+            port_values.push((symbol.to_string(), value));
+        });
+        Ok(LoadedPreset { port_values, state })
+    }
+}
+
+impl Plugin {
+    /// Get all the presets for the plugin.
+    ///
+    /// The resource of each preset is loaded to read its label and bank, since
+    /// these are usually in the preset file rather than the manifest. The
+    /// state of a preset is not read until `Preset::load` is called.
+    #[must_use]
+    pub fn presets(&self) -> Vec<Preset> {
+        let world = World::from_life(self.life.clone());
+        let uris = world.uris();
+
+        let presets = match self.related(Some(&uris.pset_preset)) {
+            Some(presets) => presets,
+            None => return Vec::new(),
+        };
+        presets
+            .iter()
+            .map(|uri| {
+                // Presets that fail to load are still listed without a label.
+                let _ = world.try_load_resource(&uri);
+                let label = world
+                    .get(Some(&uri), Some(&uris.rdfs_label), None)
+                    .and_then(|n| n.as_str().map(str::to_string));
+                let bank = world.get(Some(&uri), Some(&uris.pset_bank), None);
+                let bundle = world
+                    .get(Some(&uri), Some(&uris.rdfs_see_also), None)
+                    .and_then(|file| {
+                        let file = file.as_uri()?;
+                        let bundle = &file[..=file.rfind('/')?];
+                        Some(world.new_uri(bundle))
+                    });
+                Preset {
+                    uri: uri.clone(),
+                    label,
+                    bank,
+                    bundle,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::State;
+    use crate::urid::UridMapper;
+    use crate::world::World;
+
+    #[test]
+    fn test_preset_labels() {
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-params"))
+            .unwrap();
+        let presets = plugin.presets();
+        assert!(!presets.is_empty());
+        for preset in &presets {
+            let label = preset.label.as_deref().unwrap_or("");
+            assert!(!label.is_empty(), "{:?} has no label", preset.uri);
+        }
+    }
+
+    #[test]
+    fn test_load_preset() {
+        let world = World::with_load_all();
+        let mapper = UridMapper::new();
+        let uri = world.new_uri("http://lv2plug.in/plugins/eg-params");
+        let plugin = world.plugins().plugin(&uri).unwrap();
+        let preset = plugin.presets().into_iter().next().unwrap();
+        let loaded = preset.load(&world, mapper.map_data()).unwrap();
+
+        // eg-params only has atom ports, its parameters are in the state.
+        assert_eq!(loaded.port_values, Vec::new());
+        assert_eq!(loaded.state.plugin_uri(), uri);
+        assert_eq!(loaded.state.uri(), Some(preset.uri.clone()));
+        assert_eq!(loaded.state.label(), preset.label.as_deref());
+        assert!(loaded.state.num_properties() > 0);
+
+        let mut instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+        let save = |instance: &_| {
+            unsafe {
+                State::new_from_instance(
+                    &plugin,
+                    instance,
+                    mapper.map_data(),
+                    None,
+                    |_| None,
+                    mapper.features().iter(),
+                )
+            }
+            .unwrap()
+        };
+        let before = save(&instance);
+        let mut restored = Vec::new();
+        unsafe {
+            loaded.state.restore(
+                &mut instance,
+                mapper.map_data(),
+                |symbol, value| restored.push((symbol.to_string(), value)),
+                mapper.features().iter(),
+            );
+        }
+        assert_eq!(restored, loaded.port_values);
+        // The parameters of the instance changed to the ones of the preset.
+        let after = save(&instance);
+        assert_ne!(after, before);
+        assert!(after.num_properties() >= loaded.state.num_properties());
+    }
+}
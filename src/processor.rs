@@ -0,0 +1,261 @@
+use crate::buffer::PortBuffers;
+use crate::error::Error;
+use crate::instance::{ActiveInstance, Instance};
+use crate::worker::Worker;
+
+/// A handle for running an activated plugin instance on a realtime thread.
+///
+/// Most of the crate reads plugin data through the world, which takes a lock
+/// on every call. A `Processor` owns the instance and its preallocated port
+/// buffers and `process` never takes a lock and never allocates. Everything
+/// that needs the world, such as looking up ports or allocating buffers, must
+/// be done before the processor is created. The instance keeps the world
+/// alive until it is dropped.
+///
+/// Plugins that need the `work:schedule` feature are given a `Worker` with
+/// [`into_processor_with_worker`](ActiveInstance::into_processor_with_worker).
+/// The worker exchanges messages with its thread through lock free queues, so
+/// `process` delivers its responses without locking or allocating.
+///
+/// # Example
+/// ```no_run
+/// use lilv::buffer::PortBuffers;
+/// use lilv::feature::FeatureSet;
+/// use lilv::urid::UridMapper;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world
+///     .plugins()
+///     .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+///     .unwrap();
+/// let mapper = UridMapper::new();
+/// let features = FeatureSet::new().with_urid_mapper(&mapper);
+/// let instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+/// let buffers = PortBuffers::new(&plugin, &mapper, 64);
+/// let mut processor = unsafe { instance.activate().into_processor(buffers) };
+///
+/// std::thread::spawn(move || {
+///     processor.buffers_mut().set_control("gain", -6.0);
+///     unsafe { processor.process(64) };
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub struct Processor {
+    // The worker is dropped before the instance that it calls into, and the
+    // instance before the buffers that it is connected to.
+    worker: Option<Worker>,
+    instance: ActiveInstance,
+    buffers: PortBuffers,
+}
+
+impl ActiveInstance {
+    /// Connect `buffers` to the instance and create a `Processor` that owns
+    /// both.
+    ///
+    /// # Safety
+    /// Connecting a port calls a plugin's code, which itself may be unsafe.
+    /// The plugin must be safe to run, since `try_process` runs it without
+    /// `unsafe`.
+    #[must_use]
+    pub unsafe fn into_processor(mut self, mut buffers: PortBuffers) -> Processor {
+        buffers.connect(self.instance_mut());
+        Processor {
+            worker: None,
+            instance: self,
+            buffers,
+        }
+    }
+
+    /// Connect `buffers` to the instance and create a `Processor` that owns
+    /// both and delivers the responses of `worker` after each `process`.
+    ///
+    /// # Safety
+    /// Connecting a port calls a plugin's code, which itself may be unsafe.
+    /// The plugin must be safe to run, since `try_process` runs it without
+    /// `unsafe`. `worker` must be the worker whose feature the instance was created with
+    /// and must be attached to the instance.
+    #[must_use]
+    pub unsafe fn into_processor_with_worker(
+        self,
+        buffers: PortBuffers,
+        worker: Worker,
+    ) -> Processor {
+        let mut processor = self.into_processor(buffers);
+        processor.worker = Some(worker);
+        processor
+    }
+}
+
+impl Processor {
+    /// The maximum number of frames that can be processed at once.
+    #[must_use]
+    pub fn block_size(&self) -> usize {
+        self.buffers.block_size()
+    }
+
+    /// The port buffers that are connected to the instance.
+    #[must_use]
+    pub fn buffers(&self) -> &PortBuffers {
+        &self.buffers
+    }
+
+    /// The port buffers that are connected to the instance. Write the inputs
+    /// here before calling `process`.
+    #[must_use]
+    pub fn buffers_mut(&mut self) -> &mut PortBuffers {
+        &mut self.buffers
+    }
+
+    /// The instance that is run.
+    #[must_use]
+    pub fn instance(&self) -> &Instance {
+        self.instance.instance()
+    }
+
+    /// The worker that is run with the instance.
+    #[must_use]
+    pub fn worker(&self) -> Option<&Worker> {
+        self.worker.as_ref()
+    }
+
+    /// The latency of the plugin in frames after the last call to `process`.
+    #[must_use]
+    pub fn latency(&self) -> Option<f32> {
+        // The latency port is connected to `buffers`, which the processor owns
+        // and only lends out while it is not running.
+        unsafe { self.instance.latency() }
+    }
+
+    /// Run the instance for `frames` frames. The output atom sequences are
+    /// prepared before running and the input atom sequences are cleared
+    /// afterwards, so events written to the inputs are only sent once. The
+    /// responses of the worker are delivered after running.
+    ///
+    /// This does not lock or allocate, unless the plugin itself does.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe.
+    ///
+    /// # Panics
+    /// Panics if `frames` is greater than the block size.
+    pub unsafe fn process(&mut self, frames: usize) {
+        assert!(
+            frames <= self.buffers.block_size(),
+            "frames must not be greater than the block size"
+        );
+        self.buffers.reset_atom_outputs();
+        self.instance.run(frames);
+        if let Some(worker) = &mut self.worker {
+            worker.end_run();
+        }
+        self.buffers.reset_atom_inputs();
+    }
+
+    /// Run the instance for `frames` frames, like `process`, after checking
+    /// that `frames` fits in the buffers and that all the ports that are not
+    /// `lv2:connectionOptional` are connected.
+    ///
+    /// This is safe because the processor owns the buffers and checks that the
+    /// instance is still connected to them, so a buffer that was replaced
+    /// through `buffers_mut` is detected.
+    ///
+    /// # Errors
+    /// Returns `Error::BlockTooLarge` if `frames` is greater than the block
+    /// size and `Error::UnconnectedPorts` if ports are not connected or are
+    /// connected to data other than the buffers of the processor.
+    pub fn try_process(&mut self, frames: usize) -> Result<(), Error> {
+        let block_size = self.buffers.block_size();
+        if frames > block_size {
+            return Err(Error::BlockTooLarge { block_size, frames });
+        }
+        let instance = self.instance.instance();
+        instance.check_connected()?;
+        if !self.buffers.is_connected_to(instance) {
+            return Err(Error::UnconnectedPorts(
+                self.buffers.ports_not_connected_to(instance),
+            ));
+        }
+        // The ports are connected to `buffers`, which hold `frames` frames.
+        // Running the plugin itself was accepted as safe by `into_processor`.
+        unsafe { self.process(frames) };
+        Ok(())
+    }
+
+    /// Take the instance and the buffers that it is connected to back out of
+    /// the processor. The worker is dropped, so any work that is pending is
+    /// not delivered.
+    #[must_use]
+    pub fn into_inner(self) -> (ActiveInstance, PortBuffers) {
+        drop(self.worker);
+        (self.instance, self.buffers)
+    }
+}
+
+impl std::fmt::Debug for Processor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Processor")
+            .field("uri", &self.instance().uri())
+            .field("buffers", &self.buffers)
+            .field("worker", &self.worker)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urid::UridMapper;
+    use crate::world::World;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_amp_processor() {
+        assert_send::<Processor>();
+        let world = World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let mapper = UridMapper::new();
+        let instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+        let buffers = PortBuffers::new(&plugin, &mapper, 64);
+        let mut processor = unsafe { instance.activate().into_processor(buffers) };
+        drop(world);
+
+        let mut processor = std::thread::spawn(move || {
+            processor.buffers_mut().set_control("gain", 0.0);
+            let input = processor.buffers_mut().by_symbol_mut("in").unwrap();
+            input
+                .samples_mut()
+                .unwrap()
+                .iter_mut()
+                .for_each(|s| *s = 0.5);
+            assert_eq!(
+                processor.try_process(65),
+                Err(crate::Error::BlockTooLarge {
+                    block_size: 64,
+                    frames: 65
+                })
+            );
+            assert_eq!(processor.try_process(64), Ok(()));
+            processor
+        })
+        .join()
+        .unwrap();
+        let output = processor.buffers().by_symbol("out").unwrap();
+        assert!(output.samples().unwrap().iter().all(|s| *s == 0.5));
+
+        // Replacing the buffers disconnects the instance from them.
+        *processor.buffers_mut() = PortBuffers::new(&plugin, &mapper, 64);
+        let symbols = vec!["gain".to_string(), "in".to_string(), "out".to_string()];
+        assert_eq!(
+            processor.try_process(64),
+            Err(crate::Error::UnconnectedPorts(symbols))
+        );
+        let (instance, _buffers) = processor.into_inner();
+        drop(unsafe { instance.deactivate() });
+    }
+}
@@ -0,0 +1,354 @@
+use crate::buffer::PortBuffers;
+use crate::error::Error;
+use crate::feature::FeatureSet;
+use crate::logging::Logger;
+use crate::options::Options;
+use crate::plugin::Plugin;
+use crate::port::PortKind;
+use crate::transport::Transport;
+use crate::urid::UridMapper;
+use crate::worker::Worker;
+use std::convert::TryFrom;
+
+/// Runs a plugin over whole buffers of audio offline.
+///
+/// The renderer takes care of instantiating the plugin, splitting the audio
+/// into blocks, setting the control ports and connecting all the ports. The
+/// `urid:map`, `urid:unmap`, `work:schedule`, `log:log` and `opts:options`
+/// features are always provided. The options describe the block length,
+/// sequence size and sample rate and log messages are forwarded to the `log`
+/// crate.
+///
+/// # Example
+/// ```no_run
+/// use lilv::render::Renderer;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world
+///     .plugins()
+///     .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+///     .unwrap();
+/// let input = vec![1.0; 44100];
+/// let outputs = Renderer::new(&plugin, 44100.0, 512)
+///     .with_control("gain", -6.0)
+///     .render(&[&input])
+///     .unwrap();
+/// assert_eq!(outputs[0].len(), input.len());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    plugin: Plugin,
+    sample_rate: f64,
+    block_size: usize,
+    mapper: UridMapper,
+    features: FeatureSet,
+    controls: Vec<(String, f32)>,
+    transport: Option<Transport>,
+    compensate_latency: bool,
+}
+
+impl Renderer {
+    /// Create a new renderer for `plugin` that processes at most `block_size`
+    /// frames in each call to `run`.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is 0.
+    #[must_use]
+    pub fn new(plugin: &Plugin, sample_rate: f64, block_size: usize) -> Renderer {
+        assert!(block_size > 0, "block_size must be greater than 0");
+        let mapper = UridMapper::new();
+        Renderer {
+            plugin: plugin.clone(),
+            sample_rate,
+            block_size,
+            features: FeatureSet::new(),
+            mapper,
+            controls: Vec::new(),
+            transport: None,
+            compensate_latency: false,
+        }
+        .with_features(FeatureSet::new())
+    }
+
+    /// Provide `features` to the plugin. The renderer's own `urid:map`,
+    /// `urid:unmap`, `log:log` and `opts:options` features replace any that are
+    /// in `features`.
+    #[must_use]
+    pub fn with_features(self, features: FeatureSet) -> Renderer {
+        let mut renderer = self;
+        renderer.features = host_features(
+            features,
+            &renderer.mapper,
+            renderer.sample_rate,
+            renderer.block_size,
+        );
+        renderer
+    }
+
+    /// Set the control port with `symbol` to `value` instead of its default.
+    #[must_use]
+    pub fn with_control(self, symbol: &str, value: f32) -> Renderer {
+        let mut renderer = self;
+        renderer.set_control(symbol, value);
+        renderer
+    }
+
+    /// Set the control port with `symbol` to `value` instead of its default.
+    pub fn set_control(&mut self, symbol: &str, value: f32) {
+        self.controls.retain(|(s, _)| s != symbol);
+        self.controls.push((symbol.to_string(), value));
+    }
+
+    /// Send `transport` to the plugin's `time:Position` input, if it has one.
+    /// The transport must be created with the renderer's `urid_mapper`. Each
+    /// render starts from the state of `transport`.
+    #[must_use]
+    pub fn with_transport(self, transport: Transport) -> Renderer {
+        let mut renderer = self;
+        renderer.transport = Some(transport);
+        renderer
+    }
+
+    /// If `enabled`, remove the latency that the plugin reports from the
+    /// start of the outputs. The plugin is run for extra frames so that the
+    /// outputs still have the requested length.
+    #[must_use]
+    pub fn with_latency_compensation(self, enabled: bool) -> Renderer {
+        let mut renderer = self;
+        renderer.compensate_latency = enabled;
+        renderer
+    }
+
+    /// The plugin that is rendered.
+    #[must_use]
+    pub fn plugin(&self) -> &Plugin {
+        &self.plugin
+    }
+
+    /// The URID mapper that is provided to the plugin.
+    #[must_use]
+    pub fn urid_mapper(&self) -> &UridMapper {
+        &self.mapper
+    }
+
+    /// The features that are provided to the plugin.
+    #[must_use]
+    pub fn features(&self) -> &FeatureSet {
+        &self.features
+    }
+
+    /// Run the plugin over `inputs`, which contains one buffer for each audio
+    /// input port. The output has one buffer for each audio output port, with
+    /// as many frames as the longest input. Shorter inputs are padded with
+    /// silence.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be instantiated, if the number
+    /// of inputs does not match the number of audio input ports or if a
+    /// control override does not match a control port.
+    pub fn render(&self, inputs: &[&[f32]]) -> Result<Vec<Vec<f32>>, Error> {
+        let frames = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+        self.render_frames(inputs, frames)
+    }
+
+    /// Run the plugin for `frames` frames. This is similar to `render` but
+    /// is also useful for plugins without audio inputs, such as synthesizers.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be instantiated, if the number
+    /// of inputs does not match the number of audio input ports or if a
+    /// control override does not match a control port.
+    pub fn render_frames(&self, inputs: &[&[f32]], frames: usize) -> Result<Vec<Vec<f32>>, Error> {
+        let mut buffers = PortBuffers::new(&self.plugin, &self.mapper, self.block_size);
+        for (symbol, value) in &self.controls {
+            if !buffers.set_control(symbol, *value) {
+                return Err(Error::UnknownPort(symbol.clone()));
+            }
+        }
+        let audio_ports = |is_input: bool| -> Vec<usize> {
+            buffers
+                .iter()
+                .filter(|b| b.kind() == PortKind::Audio && b.is_input() == is_input)
+                .map(|b| b.index())
+                .collect()
+        };
+        let input_ports = audio_ports(true);
+        let output_ports = audio_ports(false);
+        if input_ports.len() != inputs.len() {
+            return Err(Error::ChannelMismatch {
+                expected: input_ports.len(),
+                actual: inputs.len(),
+            });
+        }
+
+        let time_port = self
+            .plugin
+            .time_position_port()
+            .and_then(|port| port.symbol()?.as_str().map(str::to_string));
+        let mut transport = self.transport.clone().filter(|_| time_port.is_some());
+
+        let mut worker = Worker::new_synchronous();
+        let features = self.features.clone().with_worker(&worker);
+        let mut instance = unsafe { self.plugin.try_instantiate(self.sample_rate, &features) }?;
+        unsafe {
+            buffers.connect(&mut instance);
+            worker.attach(&instance);
+        }
+        let mut instance = unsafe { instance.activate() };
+
+        let mut outputs: Vec<Vec<f32>> = output_ports
+            .iter()
+            .map(|_| Vec::with_capacity(frames))
+            .collect();
+        // The latency is only known after the plugin has run.
+        let mut latency = None;
+        let mut end = frames;
+        let mut start = 0;
+        while start < end {
+            let block = self.block_size.min(end - start);
+            for (input, index) in inputs.iter().zip(&input_ports) {
+                if let Some(samples) = buffers.get_mut(*index).and_then(|b| b.samples_mut()) {
+                    let remaining = input.get(start..).unwrap_or(&[]);
+                    let available = remaining.len().min(block);
+                    samples[..available].copy_from_slice(&remaining[..available]);
+                    samples[available..].iter_mut().for_each(|s| *s = 0.0);
+                }
+            }
+            buffers.reset_atom_sequences();
+            if let (Some(transport), Some(symbol)) = (&mut transport, &time_port) {
+                transport.write_to(&mut buffers, symbol);
+            }
+            unsafe { worker.run(&mut instance, block) };
+            if self.compensate_latency && latency.is_none() {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let frames_late = unsafe { instance.latency() }
+                    .unwrap_or(0.0)
+                    .round()
+                    .max(0.0) as usize;
+                latency = Some(frames_late);
+                end = frames + frames_late;
+            }
+            if let Some(transport) = &mut transport {
+                transport.advance(block);
+            }
+            for (output, index) in outputs.iter_mut().zip(&output_ports) {
+                if let Some(samples) = buffers.get(*index).and_then(|b| b.samples()) {
+                    output.extend_from_slice(&samples[..block]);
+                }
+            }
+            start += block;
+        }
+
+        // Deactivate and free the instance while the buffers and worker are
+        // still alive.
+        drop(unsafe { instance.deactivate() });
+        if let Some(latency) = latency {
+            for output in &mut outputs {
+                output.drain(..latency.min(output.len()));
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+/// Add the `urid:map`, `urid:unmap`, `log:log` and `opts:options` features
+/// for running with blocks of at most `block_size` frames to `features`.
+pub(crate) fn host_features(
+    features: FeatureSet,
+    mapper: &UridMapper,
+    sample_rate: f64,
+    block_size: usize,
+) -> FeatureSet {
+    let block_size = i32::try_from(block_size).unwrap_or(i32::MAX);
+    #[allow(clippy::cast_possible_truncation)]
+    let options = Options::new(mapper)
+        .with_min_block_length(1)
+        .with_max_block_length(block_size)
+        .with_nominal_block_length(block_size)
+        .with_sequence_size(PortBuffers::DEFAULT_ATOM_CAPACITY as i32)
+        .with_sample_rate(sample_rate as f32);
+    features
+        .with_urid_mapper(mapper)
+        .with_logger(&Logger::new(mapper))
+        .with_options(&options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_amp() {
+        let world = crate::World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let input: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let renderer = Renderer::new(&plugin, 44100.0, 32).with_control("gain", 0.0);
+        let outputs = renderer.render(&[&input]).unwrap();
+        assert_eq!(outputs, vec![input.clone()]);
+        let compensated = renderer.clone().with_latency_compensation(true);
+        assert_eq!(compensated.render(&[&input]).unwrap(), outputs);
+
+        assert_eq!(
+            renderer.render(&[]),
+            Err(Error::ChannelMismatch {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            renderer
+                .clone()
+                .with_control("missing", 0.0)
+                .render(&[&input]),
+            Err(Error::UnknownPort("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_render_short_inputs() {
+        let world = crate::World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let renderer = Renderer::new(&plugin, 44100.0, 32).with_control("gain", 0.0);
+        let input: Vec<f32> = (1..=40).map(|i| i as f32).collect();
+        let outputs = renderer.render_frames(&[&input], 100).unwrap();
+        assert_eq!(outputs[0].len(), 100);
+        assert_eq!(&outputs[0][..40], &input[..]);
+        assert!(outputs[0][40..].iter().all(|s| *s == 0.0));
+
+        let outputs = renderer.render_frames(&[&[]], 10).unwrap();
+        assert_eq!(outputs, vec![vec![0.0; 10]]);
+    }
+
+    #[test]
+    fn test_render_latency_compensation() {
+        // The test plugin delays its input by the 4 frames that it reports.
+        let world = crate::World::with_test_bundle();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://example.org/lilv-test"))
+            .unwrap();
+        assert_eq!(plugin.latency_port_index(), Some(5));
+        let left: Vec<f32> = (1..=20).map(|i| i as f32).collect();
+        let right = vec![1.0; 10];
+        let padded = |input: &[f32], latency: usize| {
+            let mut padded = vec![0.0; latency];
+            padded.extend_from_slice(input);
+            padded.resize(left.len(), 0.0);
+            padded
+        };
+
+        let renderer = Renderer::new(&plugin, 44100.0, 8);
+        let outputs = renderer.render(&[&left, &right]).unwrap();
+        assert_eq!(outputs, vec![padded(&left, 4), padded(&right, 4)]);
+
+        let compensated = renderer.with_latency_compensation(true);
+        let outputs = compensated.render(&[&left, &right]).unwrap();
+        assert_eq!(outputs, vec![padded(&left, 0), padded(&right, 0)]);
+    }
+}
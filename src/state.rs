@@ -0,0 +1,584 @@
+use crate::error::{c_string, Error};
+use crate::instance::Instance;
+use crate::node::Node;
+use crate::plugin::Plugin;
+use crate::urid::{map_uri, LV2UridUnmap};
+use crate::world::{Life, World};
+use lilv_sys as lib;
+use lv2_raw::{LV2Feature, LV2Urid, LV2UridMap};
+use std::ffi::{CStr, CString};
+use std::fmt::Debug;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+// Flags from the LV2 state extension.
+const LV2_STATE_IS_POD: u32 = 1;
+const LV2_STATE_IS_PORTABLE: u32 = 2;
+
+unsafe impl Send for State {}
+unsafe impl Sync for State {}
+
+/// A snapshot of the state of a plugin instance.
+///
+/// This contains the values of the plugin's ports as well as any internal
+/// state the plugin saved through the LV2 state extension.
+pub struct State {
+    pub(crate) inner: NonNull<lib::LilvStateImpl>,
+    pub(crate) life: Arc<Life>,
+}
+
+impl State {
+    /// Create a new state snapshot from a plugin instance.
+    ///
+    /// `get_value` is called for each port and should return the current value
+    /// of the port or `None` if the port should not be saved. Only control
+    /// port values are supported. The values are collected before the world
+    /// is locked, so `get_value` may use the plugin and the world.
+    ///
+    /// If the state will be saved, `dir` should be the bundle directory that
+    /// will later be passed to `save`. Any files created by the plugin will be
+    /// copied or linked into `dir`.
+    ///
+    /// The state of an activated instance can be captured by passing
+    /// `ActiveInstance::instance()`.
+    ///
+    /// # Safety
+    /// Saving the state calls the plugin's code, which itself may be unsafe.
+    /// `map` must be the same map that was passed to the instance.
+    pub unsafe fn new_from_instance<'a, F, FS>(
+        plugin: &Plugin,
+        instance: &Instance,
+        map: &LV2UridMap,
+        dir: Option<&Path>,
+        mut get_value: F,
+        features: FS,
+    ) -> Option<State>
+    where
+        F: FnMut(&str) -> Option<f32>,
+        FS: IntoIterator<Item = &'a LV2Feature>,
+    {
+        let dir = match dir {
+            Some(d) => Some(CString::new(d.to_str()?).ok()?),
+            None => None,
+        };
+        let dir_ptr = dir.as_ref().map_or(std::ptr::null(), |d| d.as_ptr());
+        let features_vec = null_terminated(features);
+        let values = plugin
+            .iter_ports()
+            .filter_map(|port| {
+                let symbol = port.symbol()?.as_str()?.to_string();
+                let value = get_value(&symbol)?;
+                Some((symbol, value))
+            })
+            .collect();
+        let mut data = GetValueData {
+            values,
+            value: 0.0,
+            float_urid: map_uri(map, lv2_raw::LV2_ATOM__FLOAT),
+        };
+
+        let _life = plugin.life.inner.lock();
+        let inner = NonNull::new(lib::lilv_state_new_from_instance(
+            plugin.inner.as_ptr(),
+            instance.inner.as_ptr(),
+            map as *const LV2UridMap as *mut _,
+            std::ptr::null(),
+            dir_ptr,
+            dir_ptr,
+            dir_ptr,
+            Some(get_port_value),
+            (&mut data as *mut GetValueData).cast(),
+            LV2_STATE_IS_POD | LV2_STATE_IS_PORTABLE,
+            features_vec.as_ptr(),
+        ))?;
+
+        Some(State {
+            inner,
+            life: plugin.life.clone(),
+        })
+    }
+
+    /// Load a state snapshot from the world.
+    ///
+    /// This can be used to load the default state of a plugin by passing the
+    /// plugin URI as `subject`, or a preset by passing the preset URI. The data
+    /// for `subject` must already be loaded, for example with
+    /// `World::load_resource`.
+    #[must_use]
+    pub fn new_from_world(world: &World, map: &LV2UridMap, subject: &Node) -> Option<State> {
+        let inner = {
+            let world = world.life.inner.lock();
+            NonNull::new(unsafe {
+                lib::lilv_state_new_from_world(
+                    world.as_ptr(),
+                    map as *const LV2UridMap as *mut _,
+                    subject.inner.as_ptr(),
+                )
+            })?
+        };
+        Some(State {
+            inner,
+            life: world.life.clone(),
+        })
+    }
+
+    /// Call `set_value` for every port value within the state.
+    ///
+    /// This is useful for applying port values without restoring the plugin's
+    /// internal state.
+    pub fn emit_port_values<F>(&self, map: &LV2UridMap, set_value: F)
+    where
+        F: FnMut(&str, f32),
+    {
+        let mut data = SetValueData::new(map, set_value);
+        unsafe {
+            lib::lilv_state_emit_port_values(
+                self.inner.as_ptr(),
+                Some(set_port_value::<F>),
+                (&mut data as *mut SetValueData<F>).cast(),
+            );
+        }
+    }
+
+    /// Restore a plugin instance from the state.
+    ///
+    /// `set_value` is called for every port value within the state. The
+    /// plugin's internal state is restored through the LV2 state extension if
+    /// the plugin supports it.
+    ///
+    /// To restore the state of an activated instance, pass
+    /// `ActiveInstance::instance_mut()`. The plugin must not run at the same
+    /// time.
+    ///
+    /// # Safety
+    /// Restoring the state calls the plugin's code, which itself may be unsafe.
+    /// `map` must be the same map that was used to create the state.
+    pub unsafe fn restore<'a, F, FS>(
+        &self,
+        instance: &mut Instance,
+        map: &LV2UridMap,
+        set_value: F,
+        features: FS,
+    ) where
+        F: FnMut(&str, f32),
+        FS: IntoIterator<Item = &'a LV2Feature>,
+    {
+        let features_vec = null_terminated(features);
+        let mut data = SetValueData::new(map, set_value);
+
+        lib::lilv_state_restore(
+            self.inner.as_ptr(),
+            instance.inner.as_ptr(),
+            Some(set_port_value::<F>),
+            (&mut data as *mut SetValueData<F>).cast(),
+            0,
+            features_vec.as_ptr(),
+        );
+    }
+
+    /// Save the state to a bundle directory on disk.
+    ///
+    /// `uri` is the URI of the state. If `None`, a URI will be generated from
+    /// `dir` and `filename`. The state is written to `filename` within `dir`
+    /// and the bundle manifest is updated.
+    ///
+    /// # Errors
+    /// Returns an error if any of the strings contain a null byte or if the
+    /// state could not be written.
+    pub fn save(
+        &self,
+        world: &World,
+        map: &LV2UridMap,
+        unmap: &LV2UridUnmap,
+        uri: Option<&str>,
+        dir: &Path,
+        filename: &str,
+    ) -> Result<(), Error> {
+        let uri = uri.map(c_string).transpose()?;
+        let dir_str = dir
+            .to_str()
+            .ok_or_else(|| Error::InvalidString(dir.to_string_lossy().into_owned()))?;
+        let dir_c = c_string(dir_str)?;
+        let filename = c_string(filename)?;
+
+        let world = world.life.inner.lock();
+        let status = unsafe {
+            lib::lilv_state_save(
+                world.as_ptr(),
+                map as *const LV2UridMap as *mut _,
+                unmap as *const LV2UridUnmap as *mut _,
+                self.inner.as_ptr(),
+                uri.as_ref().map_or(std::ptr::null(), |u| u.as_ptr()),
+                dir_c.as_ptr(),
+                filename.as_ptr(),
+            )
+        };
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(Error::SaveFailed(dir_str.to_string()))
+        }
+    }
+
+    /// The URI of the plugin the state applies to.
+    ///
+    /// # Panics
+    /// Panics if the plugin URI could not be obtained.
+    #[must_use]
+    pub fn plugin_uri(&self) -> Node {
+        let _life = self.life.inner.lock();
+        let ptr =
+            NonNull::new(unsafe { lib::lilv_state_get_plugin_uri(self.inner.as_ptr()) as _ })
+                .unwrap();
+        Node {
+            inner: ptr,
+            borrowed: true,
+            life: self.life.clone(),
+        }
+    }
+
+    /// The URI of the state or `None` if the state has not been saved.
+    #[must_use]
+    pub fn uri(&self) -> Option<Node> {
+        let _life = self.life.inner.lock();
+        let ptr = NonNull::new(unsafe { lib::lilv_state_get_uri(self.inner.as_ptr()) as _ })?;
+        Some(Node {
+            inner: ptr,
+            borrowed: true,
+            life: self.life.clone(),
+        })
+    }
+
+    /// The label of the state.
+    #[must_use]
+    pub fn label(&self) -> Option<&str> {
+        let label = unsafe { lib::lilv_state_get_label(self.inner.as_ptr()) };
+        if label.is_null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(label) }.to_str().ok()
+        }
+    }
+
+    /// Set the label of the state.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidString` if `label` contains a null byte.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        let label = c_string(label)?;
+        unsafe { lib::lilv_state_set_label(self.inner.as_ptr(), label.as_ptr()) };
+        Ok(())
+    }
+
+    /// The number of properties in the state.
+    #[must_use]
+    pub fn num_properties(&self) -> usize {
+        unsafe { lib::lilv_state_get_num_properties(self.inner.as_ptr()) as _ }
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { lib::lilv_state_equals(self.inner.as_ptr(), other.inner.as_ptr()) }
+    }
+}
+
+impl Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("uri", &self.uri())
+            .field("plugin_uri", &self.plugin_uri())
+            .field("label", &self.label())
+            .field("num_properties", &self.num_properties())
+            .finish()
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        unsafe { lib::lilv_state_free(self.inner.as_ptr()) };
+    }
+}
+
+fn null_terminated<'a, FS>(features: FS) -> Vec<*const LV2Feature>
+where
+    FS: IntoIterator<Item = &'a LV2Feature>,
+{
+    features
+        .into_iter()
+        .map(|f| f as *const LV2Feature)
+        .chain(std::iter::once(std::ptr::null()))
+        .collect()
+}
+
+// The port values of `State::new_from_instance`, collected before the world
+// is locked.
+struct GetValueData {
+    values: Vec<(String, f32)>,
+    value: f32,
+    float_urid: LV2Urid,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+unsafe extern "C" fn get_port_value(
+    port_symbol: *const c_char,
+    user_data: *mut c_void,
+    size: *mut u32,
+    type_: *mut u32,
+) -> *const c_void {
+    let data = &mut *user_data.cast::<GetValueData>();
+    let symbol = CStr::from_ptr(port_symbol).to_str().ok();
+    let value = data
+        .values
+        .iter()
+        .find(|(s, _)| Some(s.as_str()) == symbol)
+        .map(|(_, value)| *value);
+    match value {
+        Some(value) => {
+            data.value = value;
+            *size = std::mem::size_of::<f32>() as u32;
+            *type_ = data.float_urid;
+            (&data.value as *const f32).cast()
+        }
+        None => {
+            *size = 0;
+            *type_ = 0;
+            std::ptr::null()
+        }
+    }
+}
+
+/// Converts port values of any numeric atom type to `f32`.
+struct SetValueData<F> {
+    set_value: F,
+    float_urid: LV2Urid,
+    double_urid: LV2Urid,
+    int_urid: LV2Urid,
+    long_urid: LV2Urid,
+    bool_urid: LV2Urid,
+}
+
+impl<F> SetValueData<F> {
+    fn new(map: &LV2UridMap, set_value: F) -> SetValueData<F> {
+        SetValueData {
+            set_value,
+            float_urid: map_uri(map, lv2_raw::LV2_ATOM__FLOAT),
+            double_urid: map_uri(map, lv2_raw::LV2_ATOM__DOUBLE),
+            int_urid: map_uri(map, lv2_raw::LV2_ATOM__INT),
+            long_urid: map_uri(map, lv2_raw::LV2_ATOM__LONG),
+            bool_urid: map_uri(map, lv2_raw::LV2_ATOM__BOOL),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    unsafe fn decode(&self, value: *const c_void, size: u32, type_: u32) -> Option<f32> {
+        let size = size as usize;
+        if type_ == self.float_urid && size == std::mem::size_of::<f32>() {
+            Some(value.cast::<f32>().read_unaligned())
+        } else if type_ == self.double_urid && size == std::mem::size_of::<f64>() {
+            Some(value.cast::<f64>().read_unaligned() as f32)
+        } else if (type_ == self.int_urid || type_ == self.bool_urid)
+            && size == std::mem::size_of::<i32>()
+        {
+            Some(value.cast::<i32>().read_unaligned() as f32)
+        } else if type_ == self.long_urid && size == std::mem::size_of::<i64>() {
+            Some(value.cast::<i64>().read_unaligned() as f32)
+        } else {
+            None
+        }
+    }
+}
+
+unsafe extern "C" fn set_port_value<F>(
+    port_symbol: *const c_char,
+    user_data: *mut c_void,
+    value: *const c_void,
+    size: u32,
+    type_: u32,
+) where
+    F: FnMut(&str, f32),
+{
+    let data = &mut *user_data.cast::<SetValueData<F>>();
+    let symbol = match CStr::from_ptr(port_symbol).to_str() {
+        Ok(symbol) => symbol,
+        Err(_) => return,
+    };
+    if let Some(value) = data.decode(value, size, type_) {
+        (data.set_value)(symbol, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::urid::UridMapper;
+
+    #[test]
+    fn test_restore_port_values() {
+        let world = World::with_load_all();
+        let mapper = UridMapper::new();
+        let uri = world.new_uri("http://lv2plug.in/plugins/eg-amp");
+        let plugin = world
+            .plugins()
+            .plugin(&uri)
+            .unwrap_or_else(|| panic!("Could not find plugin {:?}", uri));
+        let mut instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+
+        let state = unsafe {
+            State::new_from_instance(
+                &plugin,
+                &instance,
+                mapper.map_data(),
+                None,
+                |symbol| if symbol == "gain" { Some(-6.0) } else { None },
+                mapper.features().iter(),
+            )
+        }
+        .unwrap();
+        assert_eq!(state.plugin_uri(), uri);
+
+        let mut restored = Vec::new();
+        unsafe {
+            state.restore(
+                &mut instance,
+                mapper.map_data(),
+                |symbol, value| restored.push((symbol.to_string(), value)),
+                mapper.features().iter(),
+            );
+        }
+        assert_eq!(restored, vec![("gain".to_string(), -6.0)]);
+    }
+
+    #[test]
+    fn test_active_instance_round_trip() {
+        let world = World::with_load_all();
+        let mapper = UridMapper::new();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+        let mut active = unsafe { instance.activate() };
+
+        let mut state = unsafe {
+            State::new_from_instance(
+                &plugin,
+                active.instance(),
+                mapper.map_data(),
+                None,
+                |symbol| if symbol == "gain" { Some(3.0) } else { None },
+                mapper.features().iter(),
+            )
+        }
+        .unwrap();
+        assert!(state.set_label("Loud").is_ok());
+        assert_eq!(state.label(), Some("Loud"));
+        assert_eq!(
+            state.set_label("a\0b"),
+            Err(Error::InvalidString("a\0b".to_string()))
+        );
+
+        let mut restored = Vec::new();
+        unsafe {
+            state.restore(
+                active.instance_mut(),
+                mapper.map_data(),
+                |symbol, value| restored.push((symbol.to_string(), value)),
+                mapper.features().iter(),
+            );
+        }
+        assert_eq!(restored, vec![("gain".to_string(), 3.0)]);
+    }
+
+    #[test]
+    fn test_get_value_can_use_the_world() {
+        let world = World::with_load_all();
+        let mapper = UridMapper::new();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+
+        // Looking up the port locks the world, which must not be locked yet.
+        let get_value = |symbol: &str| {
+            let port = plugin.port_by_symbol(&world.new_string(symbol))?;
+            let range = port.range();
+            range.default.and_then(|d| d.as_float())
+        };
+        let state = unsafe {
+            State::new_from_instance(
+                &plugin,
+                &instance,
+                mapper.map_data(),
+                None,
+                get_value,
+                mapper.features().iter(),
+            )
+        }
+        .unwrap();
+
+        let mut values = Vec::new();
+        state.emit_port_values(mapper.map_data(), |symbol, value| {
+            values.push((symbol.to_string(), value));
+        });
+        assert_eq!(values, vec![("gain".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let world = World::with_load_all();
+        let mapper = UridMapper::new();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+            .unwrap();
+        let instance =
+            unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+        let dir = std::env::temp_dir().join(format!("lilv-test-state-{}.lv2", std::process::id()));
+        let state = unsafe {
+            State::new_from_instance(
+                &plugin,
+                &instance,
+                mapper.map_data(),
+                Some(&dir),
+                |symbol| if symbol == "gain" { Some(-12.0) } else { None },
+                mapper.features().iter(),
+            )
+        }
+        .unwrap();
+
+        let uri = "http://example.org/lilv-test-state";
+        state
+            .save(
+                &world,
+                mapper.map_data(),
+                mapper.unmap_data(),
+                Some(uri),
+                &dir,
+                "state.ttl",
+            )
+            .unwrap();
+        assert!(dir.join("manifest.ttl").is_file());
+        assert!(dir.join("state.ttl").is_file());
+
+        let bundle = world.new_file_uri(None, &format!("{}/", dir.display()));
+        world.load_bundle(&bundle);
+        let subject = world.new_uri(uri);
+        world.try_load_resource(&subject).unwrap();
+        let loaded = State::new_from_world(&world, mapper.map_data(), &subject).unwrap();
+        assert_eq!(loaded.plugin_uri(), plugin.uri());
+        let mut values = Vec::new();
+        loaded.emit_port_values(mapper.map_data(), |symbol, value| {
+            values.push((symbol.to_string(), value));
+        });
+        assert_eq!(values, vec![("gain".to_string(), -12.0)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+use crate::atom::{AtomValue, SequenceWriter};
+use crate::buffer::PortBuffers;
+use crate::plugin::Plugin;
+use crate::port::{Port, PortDirection, PortKind};
+use crate::urid::UridMapper;
+use crate::world::World;
+use lv2_raw::LV2Urid;
+
+/// The URI for `time:Position`.
+pub const TIME_POSITION_URI: &str = "http://lv2plug.in/ns/ext/time#Position";
+
+impl Plugin {
+    /// Get the atom input port that receives `time:Position` objects.
+    #[must_use]
+    pub fn time_position_port(&self) -> Option<Port> {
+        let world = World::from_life(self.life.clone());
+        let position = &world.uris().time_position;
+        self.iter_ports().find(|port| {
+            port.direction() == PortDirection::Input
+                && port.kind() == PortKind::Atom
+                && port.supports_event(position)
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TimeUrids {
+    position: LV2Urid,
+    frame: LV2Urid,
+    speed: LV2Urid,
+    bar: LV2Urid,
+    bar_beat: LV2Urid,
+    beat: LV2Urid,
+    beat_unit: LV2Urid,
+    beats_per_bar: LV2Urid,
+    beats_per_minute: LV2Urid,
+}
+
+impl TimeUrids {
+    fn new(mapper: &UridMapper) -> TimeUrids {
+        let time = |name: &str| mapper.map(&format!("http://lv2plug.in/ns/ext/time#{}", name));
+        TimeUrids {
+            position: mapper.map(TIME_POSITION_URI),
+            frame: time("frame"),
+            speed: time("speed"),
+            bar: time("bar"),
+            bar_beat: time("barBeat"),
+            beat: time("beat"),
+            beat_unit: time("beatUnit"),
+            beats_per_bar: time("beatsPerBar"),
+            beats_per_minute: time("beatsPerMinute"),
+        }
+    }
+}
+
+/// Tracks the host transport and sends it to plugins as `time:Position`
+/// objects.
+///
+/// The position is written at the start of a block whenever the transport
+/// changed, or at every block if `set_update_every_block` is enabled. Call
+/// `advance` after each `run` to move the transport forward.
+///
+/// # Example
+/// ```no_run
+/// use lilv::buffer::PortBuffers;
+/// use lilv::feature::FeatureSet;
+/// use lilv::transport::Transport;
+/// use lilv::urid::UridMapper;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world.plugins().iter().next().unwrap();
+/// let mapper = UridMapper::new();
+/// let features = FeatureSet::new().with_urid_mapper(&mapper);
+/// let mut buffers = PortBuffers::new(&plugin, &mapper, 512);
+/// let symbol = plugin.time_position_port().unwrap().symbol().unwrap();
+/// let mut transport = Transport::new(&mapper, 48000.0);
+/// transport.set_tempo(96.0);
+/// transport.play();
+///
+/// let mut instance = unsafe { plugin.instantiate_with_features(48000.0, &features) }.unwrap();
+/// unsafe { buffers.connect(&mut instance) };
+/// let mut instance = unsafe { instance.activate() };
+/// for _ in 0..100 {
+///     buffers.reset_atom_sequences();
+///     transport.write_to(&mut buffers, symbol.as_str().unwrap());
+///     unsafe { instance.run(512) };
+///     transport.advance(512);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Transport {
+    urids: TimeUrids,
+    sample_rate: f64,
+    // Kept fractional so that `frame` does not drift from `beat` at speeds
+    // that advance by a non-integer number of frames per block.
+    frame: f64,
+    beat: f64,
+    speed: f32,
+    beats_per_minute: f32,
+    beats_per_bar: f32,
+    beat_unit: i32,
+    every_block: bool,
+    changed: bool,
+}
+
+impl Transport {
+    /// Create a stopped transport at frame 0 with 120 BPM in 4/4. `mapper`
+    /// must be the mapper that is passed to the plugin.
+    #[must_use]
+    pub fn new(mapper: &UridMapper, sample_rate: f64) -> Transport {
+        Transport {
+            urids: TimeUrids::new(mapper),
+            sample_rate,
+            frame: 0.0,
+            beat: 0.0,
+            speed: 0.0,
+            beats_per_minute: 120.0,
+            beats_per_bar: 4.0,
+            beat_unit: 4,
+            every_block: false,
+            changed: true,
+        }
+    }
+
+    /// The position of the transport in frames.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn frame(&self) -> i64 {
+        self.frame.floor() as i64
+    }
+
+    /// The position of the transport in beats since frame 0.
+    #[must_use]
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+
+    /// The current bar, starting from 0.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn bar(&self) -> i64 {
+        (self.beat / f64::from(self.beats_per_bar)).floor() as i64
+    }
+
+    /// The beat within the current bar.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn bar_beat(&self) -> f32 {
+        self.beat.rem_euclid(f64::from(self.beats_per_bar)) as f32
+    }
+
+    /// The playback speed. 0 is stopped and 1 is playing at normal speed.
+    #[must_use]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Returns `true` if the speed is not 0.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.speed != 0.0
+    }
+
+    /// The tempo in beats per minute.
+    #[must_use]
+    pub fn beats_per_minute(&self) -> f32 {
+        self.beats_per_minute
+    }
+
+    /// The time signature as beats per bar and the note value of a beat.
+    #[must_use]
+    pub fn time_signature(&self) -> (f32, i32) {
+        (self.beats_per_bar, self.beat_unit)
+    }
+
+    /// Returns `true` if the transport changed since the position was last
+    /// written.
+    #[must_use]
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Start playing at normal speed.
+    pub fn play(&mut self) {
+        self.set_speed(1.0);
+    }
+
+    /// Stop playing. The position is kept.
+    pub fn stop(&mut self) {
+        self.set_speed(0.0);
+    }
+
+    /// Set the playback speed.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.changed |= (self.speed - speed).abs() > f32::EPSILON;
+        self.speed = speed;
+    }
+
+    /// Set the tempo in beats per minute.
+    pub fn set_tempo(&mut self, beats_per_minute: f32) {
+        self.changed |= (self.beats_per_minute - beats_per_minute).abs() > f32::EPSILON;
+        self.beats_per_minute = beats_per_minute;
+    }
+
+    /// Set the time signature, for example `(3.0, 4)` for 3/4.
+    pub fn set_time_signature(&mut self, beats_per_bar: f32, beat_unit: i32) {
+        self.changed |= (beats_per_bar, beat_unit) != (self.beats_per_bar, self.beat_unit);
+        self.beats_per_bar = beats_per_bar;
+        self.beat_unit = beat_unit;
+    }
+
+    /// Move the transport to `frame`. The beat is computed with the current
+    /// tempo.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn seek(&mut self, frame: i64) {
+        self.frame = frame as f64;
+        self.beat = self.frame / self.sample_rate * f64::from(self.beats_per_minute) / 60.0;
+        self.changed = true;
+    }
+
+    /// If `every_block` is `true`, the position is written at the start of
+    /// every block instead of only when the transport changes.
+    pub fn set_update_every_block(&mut self, every_block: bool) {
+        self.every_block = every_block;
+    }
+
+    /// Move the transport forward by `frames` at the current speed. Call this
+    /// after each `run`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn advance(&mut self, frames: usize) {
+        let frames = frames as f64 * f64::from(self.speed);
+        self.frame += frames;
+        self.beat += frames / self.sample_rate * f64::from(self.beats_per_minute) / 60.0;
+    }
+
+    /// Write the position at frame 0 of `writer` if the transport changed or
+    /// if the position is written every block. This must be called before any
+    /// other events are written to `writer`.
+    ///
+    /// Returns `false` if the position had to be written but did not fit.
+    pub fn write(&mut self, writer: &mut SequenceWriter) -> bool {
+        if !self.changed && !self.every_block {
+            return true;
+        }
+        let u = self.urids;
+        let written = writer.write_object(
+            0,
+            0,
+            u.position,
+            &[
+                (u.frame, AtomValue::Long(self.frame())),
+                (u.speed, AtomValue::Float(self.speed)),
+                (u.bar, AtomValue::Long(self.bar())),
+                (u.bar_beat, AtomValue::Float(self.bar_beat())),
+                (u.beat, AtomValue::Double(self.beat)),
+                (u.beat_unit, AtomValue::Int(self.beat_unit)),
+                (u.beats_per_bar, AtomValue::Float(self.beats_per_bar)),
+                (u.beats_per_minute, AtomValue::Float(self.beats_per_minute)),
+            ],
+        );
+        if written {
+            self.changed = false;
+        }
+        written
+    }
+
+    /// Replace the contents of the atom port with `symbol` in `buffers` and
+    /// write the position with `write`. To also send other events, such as
+    /// MIDI, get a writer with `PortBuffers::sequence_writer` and call `write`
+    /// first.
+    ///
+    /// Returns `false` if there is no atom port with `symbol` or the position
+    /// did not fit.
+    pub fn write_to(&mut self, buffers: &mut PortBuffers, symbol: &str) -> bool {
+        match buffers.sequence_writer(symbol) {
+            Some(mut writer) => self.write(&mut writer),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom::{AtomUrids, SequenceBuffer, SequenceIter};
+
+    #[test]
+    fn test_write_position() {
+        let mapper = UridMapper::new();
+        let urids = AtomUrids::new(&mapper);
+        let mut transport = Transport::new(&mapper, 48000.0);
+        transport.set_time_signature(3.0, 4);
+        transport.play();
+        transport.advance(96000);
+        assert_eq!(transport.frame(), 96000);
+        assert_eq!(transport.bar(), 1);
+        assert!((transport.bar_beat() - 1.0).abs() < 1e-6);
+
+        let mut buffer = SequenceBuffer::new(512);
+        let bytes = buffer.bytes_mut();
+        let mut writer = SequenceWriter::new(bytes, &urids).unwrap();
+        assert!(transport.write(&mut writer));
+        assert!(!transport.is_changed());
+
+        let event = SequenceIter::new(bytes, &urids).unwrap().next().unwrap();
+        let object = event.atom.object(&urids).unwrap();
+        assert_eq!(object.otype, mapper.map(TIME_POSITION_URI));
+        let bpm = object.get(mapper.map("http://lv2plug.in/ns/ext/time#beatsPerMinute"));
+        assert_eq!(bpm.unwrap().value(&urids), AtomValue::Float(120.0));
+    }
+
+    #[test]
+    fn test_advance_at_half_speed() {
+        let mapper = UridMapper::new();
+        let mut transport = Transport::new(&mapper, 48000.0);
+        transport.set_speed(0.5);
+        for _ in 0..1000 {
+            transport.advance(3);
+        }
+        assert_eq!(transport.frame(), 1500);
+        let beat = 1500.0 / 48000.0 * 120.0 / 60.0;
+        assert!((transport.beat() - beat).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::feature::FeatureSet;
+use lv2_raw::{LV2Feature, LV2Urid, LV2UridMap};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt::Debug;
+use std::os::raw::{c_char, c_void};
+use std::sync::Arc;
+
+/// The URI for the `urid:map` feature.
+pub const URID_MAP_URI: &str = "http://lv2plug.in/ns/ext/urid#map";
+/// The URI for the `urid:unmap` feature.
+pub const URID_UNMAP_URI: &str = "http://lv2plug.in/ns/ext/urid#unmap";
+
+const URID_MAP_URI_C: &[u8] = b"http://lv2plug.in/ns/ext/urid#map\0";
+const URID_UNMAP_URI_C: &[u8] = b"http://lv2plug.in/ns/ext/urid#unmap\0";
+
+/// Opaque pointer to host data for `LV2UridUnmap`.
+pub type LV2UridUnmapHandle = *mut c_void;
+
+/// URID Unmap Feature (`urid:unmap`).
+///
+/// This is not provided by `lv2_raw` so it is defined here.
+#[repr(C)]
+pub struct LV2UridUnmap {
+    /// Opaque pointer to host data. This must be passed to `unmap` whenever it
+    /// is called.
+    pub handle: LV2UridUnmapHandle,
+
+    /// Get the URI for a previously mapped numeric ID. Returns null if `urid`
+    /// is not yet mapped.
+    pub unmap: extern "C" fn(handle: LV2UridUnmapHandle, urid: LV2Urid) -> *const c_char,
+}
+
+/// Map a null terminated `uri` with `map`.
+pub(crate) fn map_uri(map: &LV2UridMap, uri: &[u8]) -> LV2Urid {
+    debug_assert_eq!(uri.last(), Some(&0), "uri must be null terminated");
+    (map.map)(map.handle, uri.as_ptr().cast())
+}
+
+/// A thread safe implementation of the `urid:map` and `urid:unmap` features.
+///
+/// Cloning a `UridMapper` is cheap and all clones share the same mappings. The
+/// features must outlive any plugin instance they are passed to, so a clone of
+/// the mapper should be kept for as long as the instance is alive.
+///
+/// # Example
+/// ```
+/// let mapper = lilv::urid::UridMapper::new();
+/// let urid = mapper.map("http://lv2plug.in/ns/ext/atom#Float");
+/// assert_eq!(mapper.unmap(urid), Some("http://lv2plug.in/ns/ext/atom#Float"));
+/// ```
+#[derive(Clone)]
+pub struct UridMapper {
+    inner: Arc<MapperInner>,
+}
+
+struct MapperInner {
+    table: Mutex<UridTable>,
+    map: LV2UridMap,
+    unmap: LV2UridUnmap,
+    map_feature: LV2Feature,
+    unmap_feature: LV2Feature,
+}
+
+// The raw pointers within `MapperInner` all point to data owned by
+// `MapperInner` and the mutable state is guarded by `table`.
+unsafe impl Send for MapperInner {}
+unsafe impl Sync for MapperInner {}
+
+#[derive(Default)]
+struct UridTable {
+    urids: HashMap<CString, LV2Urid>,
+    // The URI for the URID `n` is stored at index `n - 1`.
+    uris: Vec<CString>,
+}
+
+impl UridTable {
+    #[allow(clippy::cast_possible_truncation)]
+    fn map(&mut self, uri: &CStr) -> LV2Urid {
+        if let Some(urid) = self.urids.get(uri) {
+            return *urid;
+        }
+        let uri = uri.to_owned();
+        self.uris.push(uri.clone());
+        let urid = self.uris.len() as LV2Urid;
+        self.urids.insert(uri, urid);
+        urid
+    }
+
+    fn unmap(&self, urid: LV2Urid) -> Option<&CStr> {
+        let index = (urid as usize).checked_sub(1)?;
+        self.uris.get(index).map(CString::as_c_str)
+    }
+}
+
+impl UridMapper {
+    /// Create a new mapper with no mapped URIs.
+    #[must_use]
+    pub fn new() -> UridMapper {
+        let mut inner = Arc::new(MapperInner {
+            table: Mutex::new(UridTable::default()),
+            map: LV2UridMap {
+                handle: std::ptr::null_mut(),
+                map: urid_map,
+            },
+            unmap: LV2UridUnmap {
+                handle: std::ptr::null_mut(),
+                unmap: urid_unmap,
+            },
+            map_feature: LV2Feature {
+                uri: URID_MAP_URI_C.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            unmap_feature: LV2Feature {
+                uri: URID_UNMAP_URI_C.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+        });
+        let handle = Arc::as_ptr(&inner) as *mut c_void;
+        // `inner` has not been shared yet so it is always unique.
+        if let Some(inner_mut) = Arc::get_mut(&mut inner) {
+            inner_mut.map.handle = handle;
+            inner_mut.unmap.handle = handle;
+            inner_mut.map_feature.data = (&mut inner_mut.map as *mut LV2UridMap).cast();
+            inner_mut.unmap_feature.data = (&mut inner_mut.unmap as *mut LV2UridUnmap).cast();
+        }
+        UridMapper { inner }
+    }
+
+    /// Get the URID for `uri`, creating a new one if it does not exist. Returns
+    /// `0` if `uri` contains a null byte.
+    #[must_use]
+    pub fn map(&self, uri: &str) -> LV2Urid {
+        match CString::new(uri) {
+            Ok(uri) => self.inner.table.lock().map(&uri),
+            Err(_) => 0,
+        }
+    }
+
+    /// Get the URI for `urid` or `None` if it has not been mapped.
+    #[must_use]
+    pub fn unmap(&self, urid: LV2Urid) -> Option<&str> {
+        let table = self.inner.table.lock();
+        let uri = table.unmap(urid)?.as_ptr();
+        // Mapped URIs are never removed and the `CString` data does not move so
+        // it is valid for the lifetime of `self`.
+        unsafe { CStr::from_ptr(uri) }.to_str().ok()
+    }
+
+    /// The `urid:map` feature.
+    #[must_use]
+    pub fn map_feature(&self) -> &LV2Feature {
+        &self.inner.map_feature
+    }
+
+    /// The `urid:unmap` feature.
+    #[must_use]
+    pub fn unmap_feature(&self) -> &LV2Feature {
+        &self.inner.unmap_feature
+    }
+
+    /// Both the `urid:map` and `urid:unmap` features. The set keeps the
+    /// mapper alive, so it can be passed to `Plugin::instantiate_with_features`
+    /// and the instance may outlive `self`.
+    #[must_use]
+    pub fn features(&self) -> FeatureSet {
+        FeatureSet::new().with_urid_mapper(self)
+    }
+
+    /// The raw `urid:map` data.
+    #[must_use]
+    pub fn map_data(&self) -> &LV2UridMap {
+        &self.inner.map
+    }
+
+    /// The raw `urid:unmap` data.
+    #[must_use]
+    pub fn unmap_data(&self) -> &LV2UridUnmap {
+        &self.inner.unmap
+    }
+}
+
+impl Default for UridMapper {
+    fn default() -> UridMapper {
+        UridMapper::new()
+    }
+}
+
+impl Debug for UridMapper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UridMapper")
+            .field("mapped", &self.inner.table.lock().uris.len())
+            .finish()
+    }
+}
+
+extern "C" fn urid_map(handle: *mut c_void, uri: *const c_char) -> LV2Urid {
+    if handle.is_null() || uri.is_null() {
+        return 0;
+    }
+    let inner = unsafe { &*handle.cast::<MapperInner>() };
+    let uri = unsafe { CStr::from_ptr(uri) };
+    inner.table.lock().map(uri)
+}
+
+extern "C" fn urid_unmap(handle: *mut c_void, urid: LV2Urid) -> *const c_char {
+    if handle.is_null() {
+        return std::ptr::null();
+    }
+    let inner = unsafe { &*handle.cast::<MapperInner>() };
+    inner
+        .table
+        .lock()
+        .unmap(urid)
+        .map_or(std::ptr::null(), CStr::as_ptr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_unmap() {
+        let mapper = UridMapper::new();
+        let a = mapper.map("http://example.com/a");
+        let b = mapper.map("http://example.com/b");
+        assert_ne!(a, 0);
+        assert_ne!(a, b);
+        assert_eq!(mapper.map("http://example.com/a"), a);
+        assert_eq!(mapper.unmap(a), Some("http://example.com/a"));
+        assert_eq!(mapper.unmap(b), Some("http://example.com/b"));
+        assert_eq!(mapper.unmap(0), None);
+        assert_eq!(mapper.unmap(b + 1), None);
+    }
+
+    #[test]
+    fn test_features() {
+        let mapper = UridMapper::new();
+        let uris: Vec<_> = mapper
+            .features()
+            .iter()
+            .map(|f| unsafe { CStr::from_ptr(f.uri) }.to_str().unwrap())
+            .collect();
+        assert_eq!(uris, vec![URID_MAP_URI, URID_UNMAP_URI]);
+
+        let map = unsafe { &*mapper.map_feature().data.cast::<LV2UridMap>() };
+        let urid = map_uri(map, b"http://example.com/a\0");
+        assert_eq!(mapper.map("http://example.com/a"), urid);
+
+        let unmap = unsafe { &*mapper.unmap_feature().data.cast::<LV2UridUnmap>() };
+        let uri = unsafe { CStr::from_ptr((unmap.unmap)(unmap.handle, urid)) };
+        assert_eq!(uri.to_str(), Ok("http://example.com/a"));
+
+        // The features keep the mapper alive.
+        let features = mapper.features();
+        drop(mapper);
+        let map = features.iter().next().unwrap().data.cast::<LV2UridMap>();
+        assert_eq!(map_uri(unsafe { &*map }, b"http://example.com/a\0"), urid);
+    }
+}
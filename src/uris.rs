@@ -0,0 +1,239 @@
+use crate::node::Node;
+use crate::world::Life;
+use lilv_sys as lib;
+use std::fmt::Debug;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+macro_rules! uris {
+    ($($field:ident => $uri:expr,)*) => {
+        /// Nodes for well known URIs of the LV2 specifications.
+        ///
+        /// The nodes are created once per world and shared by all of its
+        /// handles. Get the table with `World::uris`.
+        ///
+        /// # Example
+        /// ```
+        /// let world = lilv::World::new();
+        /// let uris = world.uris();
+        /// assert_eq!(
+        ///     uris.lv2_audio_port.as_uri(),
+        ///     Some("http://lv2plug.in/ns/lv2core#AudioPort")
+        /// );
+        /// ```
+        #[derive(Clone)]
+        pub struct Uris {
+            $(
+                #[doc = concat!("`<", $uri, ">`")]
+                pub $field: Node,
+            )*
+        }
+
+        impl Uris {
+            /// Wrap the nodes of the table of `life`. The nodes are borrowed
+            /// from the table and each one keeps `life` alive.
+            pub(crate) fn new(life: &Arc<Life>) -> Uris {
+                let table = life.uri_table();
+                Uris {
+                    $($field: Node {
+                        inner: table.$field,
+                        borrowed: true,
+                        life: life.clone(),
+                    },)*
+                }
+            }
+        }
+
+        // The raw nodes behind `Uris`. These are owned by `Life` so that the
+        // crate can use them without creating a reference cycle.
+        pub(crate) struct UriTable {
+            $(pub(crate) $field: NonNull<lib::LilvNodeImpl>,)*
+        }
+
+        impl UriTable {
+            /// Create the nodes within `world`.
+            ///
+            /// # Panics
+            /// Panics if a node could not be created.
+            pub(crate) fn new(world: NonNull<lib::LilvWorldImpl>) -> UriTable {
+                UriTable {
+                    $($field: NonNull::new(unsafe {
+                        lib::lilv_new_uri(world.as_ptr(), concat!($uri, "\0").as_ptr().cast())
+                    })
+                    .unwrap(),)*
+                }
+            }
+
+            /// Free all the nodes. The table must not be used afterwards.
+            pub(crate) unsafe fn free(&self) {
+                $(lib::lilv_node_free(self.$field.as_ptr());)*
+            }
+        }
+    };
+}
+
+uris! {
+    lv2_plugin => "http://lv2plug.in/ns/lv2core#Plugin",
+    lv2_port => "http://lv2plug.in/ns/lv2core#Port",
+    lv2_input_port => "http://lv2plug.in/ns/lv2core#InputPort",
+    lv2_output_port => "http://lv2plug.in/ns/lv2core#OutputPort",
+    lv2_control_port => "http://lv2plug.in/ns/lv2core#ControlPort",
+    lv2_audio_port => "http://lv2plug.in/ns/lv2core#AudioPort",
+    lv2_cv_port => "http://lv2plug.in/ns/lv2core#CVPort",
+    lv2_symbol => "http://lv2plug.in/ns/lv2core#symbol",
+    lv2_name => "http://lv2plug.in/ns/lv2core#name",
+    lv2_index => "http://lv2plug.in/ns/lv2core#index",
+    lv2_default => "http://lv2plug.in/ns/lv2core#default",
+    lv2_minimum => "http://lv2plug.in/ns/lv2core#minimum",
+    lv2_maximum => "http://lv2plug.in/ns/lv2core#maximum",
+    lv2_scale_point => "http://lv2plug.in/ns/lv2core#scalePoint",
+    lv2_port_property => "http://lv2plug.in/ns/lv2core#portProperty",
+    lv2_designation => "http://lv2plug.in/ns/lv2core#designation",
+    lv2_control => "http://lv2plug.in/ns/lv2core#control",
+    lv2_latency => "http://lv2plug.in/ns/lv2core#latency",
+    lv2_free_wheeling => "http://lv2plug.in/ns/lv2core#freeWheeling",
+    lv2_enabled => "http://lv2plug.in/ns/lv2core#enabled",
+    lv2_toggled => "http://lv2plug.in/ns/lv2core#toggled",
+    lv2_integer => "http://lv2plug.in/ns/lv2core#integer",
+    lv2_enumeration => "http://lv2plug.in/ns/lv2core#enumeration",
+    lv2_sample_rate => "http://lv2plug.in/ns/lv2core#sampleRate",
+    lv2_connection_optional => "http://lv2plug.in/ns/lv2core#connectionOptional",
+    lv2_reports_latency => "http://lv2plug.in/ns/lv2core#reportsLatency",
+    lv2_required_feature => "http://lv2plug.in/ns/lv2core#requiredFeature",
+    lv2_optional_feature => "http://lv2plug.in/ns/lv2core#optionalFeature",
+    lv2_extension_data => "http://lv2plug.in/ns/lv2core#extensionData",
+    lv2_applies_to => "http://lv2plug.in/ns/lv2core#appliesTo",
+    lv2_minor_version => "http://lv2plug.in/ns/lv2core#minorVersion",
+    lv2_micro_version => "http://lv2plug.in/ns/lv2core#microVersion",
+    lv2_project => "http://lv2plug.in/ns/lv2core#project",
+    lv2_hard_rt_capable => "http://lv2plug.in/ns/lv2core#hardRTCapable",
+    lv2_in_place_broken => "http://lv2plug.in/ns/lv2core#inPlaceBroken",
+    lv2_is_live => "http://lv2plug.in/ns/lv2core#isLive",
+    atom_atom_port => "http://lv2plug.in/ns/ext/atom#AtomPort",
+    atom_buffer_type => "http://lv2plug.in/ns/ext/atom#bufferType",
+    atom_supports => "http://lv2plug.in/ns/ext/atom#supports",
+    atom_sequence => "http://lv2plug.in/ns/ext/atom#Sequence",
+    atom_chunk => "http://lv2plug.in/ns/ext/atom#Chunk",
+    atom_object => "http://lv2plug.in/ns/ext/atom#Object",
+    atom_blank => "http://lv2plug.in/ns/ext/atom#Blank",
+    atom_resource => "http://lv2plug.in/ns/ext/atom#Resource",
+    atom_int => "http://lv2plug.in/ns/ext/atom#Int",
+    atom_long => "http://lv2plug.in/ns/ext/atom#Long",
+    atom_float => "http://lv2plug.in/ns/ext/atom#Float",
+    atom_double => "http://lv2plug.in/ns/ext/atom#Double",
+    atom_bool => "http://lv2plug.in/ns/ext/atom#Bool",
+    atom_urid => "http://lv2plug.in/ns/ext/atom#URID",
+    atom_string => "http://lv2plug.in/ns/ext/atom#String",
+    atom_path => "http://lv2plug.in/ns/ext/atom#Path",
+    atom_uri => "http://lv2plug.in/ns/ext/atom#URI",
+    atom_vector => "http://lv2plug.in/ns/ext/atom#Vector",
+    atom_tuple => "http://lv2plug.in/ns/ext/atom#Tuple",
+    midi_midi_event => "http://lv2plug.in/ns/ext/midi#MidiEvent",
+    patch_message => "http://lv2plug.in/ns/ext/patch#Message",
+    patch_get => "http://lv2plug.in/ns/ext/patch#Get",
+    patch_set => "http://lv2plug.in/ns/ext/patch#Set",
+    patch_put => "http://lv2plug.in/ns/ext/patch#Put",
+    patch_patch => "http://lv2plug.in/ns/ext/patch#Patch",
+    patch_body => "http://lv2plug.in/ns/ext/patch#body",
+    patch_property => "http://lv2plug.in/ns/ext/patch#property",
+    patch_subject => "http://lv2plug.in/ns/ext/patch#subject",
+    patch_value => "http://lv2plug.in/ns/ext/patch#value",
+    patch_writable => "http://lv2plug.in/ns/ext/patch#writable",
+    patch_readable => "http://lv2plug.in/ns/ext/patch#readable",
+    pset_preset => "http://lv2plug.in/ns/ext/presets#Preset",
+    pset_bank => "http://lv2plug.in/ns/ext/presets#bank",
+    pset_value => "http://lv2plug.in/ns/ext/presets#value",
+    state_interface => "http://lv2plug.in/ns/ext/state#interface",
+    state_state => "http://lv2plug.in/ns/ext/state#state",
+    state_load_default_state => "http://lv2plug.in/ns/ext/state#loadDefaultState",
+    state_thread_safe_restore => "http://lv2plug.in/ns/ext/state#threadSafeRestore",
+    ui_ui => "http://lv2plug.in/ns/extensions/ui#UI",
+    ui_x11_ui => "http://lv2plug.in/ns/extensions/ui#X11UI",
+    ui_gtk_ui => "http://lv2plug.in/ns/extensions/ui#GtkUI",
+    ui_gtk3_ui => "http://lv2plug.in/ns/extensions/ui#Gtk3UI",
+    ui_qt5_ui => "http://lv2plug.in/ns/extensions/ui#Qt5UI",
+    ui_cocoa_ui => "http://lv2plug.in/ns/extensions/ui#CocoaUI",
+    ui_windows_ui => "http://lv2plug.in/ns/extensions/ui#WindowsUI",
+    ui_show_interface => "http://lv2plug.in/ns/extensions/ui#showInterface",
+    ui_idle_interface => "http://lv2plug.in/ns/extensions/ui#idleInterface",
+    ui_resize => "http://lv2plug.in/ns/extensions/ui#resize",
+    ui_parent => "http://lv2plug.in/ns/extensions/ui#parent",
+    ui_port_notification => "http://lv2plug.in/ns/extensions/ui#portNotification",
+    ui_plugin => "http://lv2plug.in/ns/extensions/ui#plugin",
+    units_unit => "http://lv2plug.in/ns/extensions/units#unit",
+    units_db => "http://lv2plug.in/ns/extensions/units#db",
+    units_hz => "http://lv2plug.in/ns/extensions/units#hz",
+    units_khz => "http://lv2plug.in/ns/extensions/units#khz",
+    units_ms => "http://lv2plug.in/ns/extensions/units#ms",
+    units_s => "http://lv2plug.in/ns/extensions/units#s",
+    units_pc => "http://lv2plug.in/ns/extensions/units#pc",
+    units_bpm => "http://lv2plug.in/ns/extensions/units#bpm",
+    units_beat => "http://lv2plug.in/ns/extensions/units#beat",
+    units_semitone_12tet => "http://lv2plug.in/ns/extensions/units#semitone12TET",
+    units_midi_note => "http://lv2plug.in/ns/extensions/units#midiNote",
+    units_frame => "http://lv2plug.in/ns/extensions/units#frame",
+    time_position => "http://lv2plug.in/ns/ext/time#Position",
+    time_bar => "http://lv2plug.in/ns/ext/time#bar",
+    time_bar_beat => "http://lv2plug.in/ns/ext/time#barBeat",
+    time_beat => "http://lv2plug.in/ns/ext/time#beat",
+    time_beat_unit => "http://lv2plug.in/ns/ext/time#beatUnit",
+    time_beats_per_bar => "http://lv2plug.in/ns/ext/time#beatsPerBar",
+    time_beats_per_minute => "http://lv2plug.in/ns/ext/time#beatsPerMinute",
+    time_frame => "http://lv2plug.in/ns/ext/time#frame",
+    time_frames_per_second => "http://lv2plug.in/ns/ext/time#framesPerSecond",
+    time_speed => "http://lv2plug.in/ns/ext/time#speed",
+    pprops_logarithmic => "http://lv2plug.in/ns/ext/port-props#logarithmic",
+    pprops_not_on_gui => "http://lv2plug.in/ns/ext/port-props#notOnGUI",
+    pprops_has_strict_bounds => "http://lv2plug.in/ns/ext/port-props#hasStrictBounds",
+    pprops_expensive => "http://lv2plug.in/ns/ext/port-props#expensive",
+    pprops_causes_artifacts => "http://lv2plug.in/ns/ext/port-props#causesArtifacts",
+    pprops_continuous_cv => "http://lv2plug.in/ns/ext/port-props#continuousCV",
+    pprops_discrete_cv => "http://lv2plug.in/ns/ext/port-props#discreteCV",
+    pprops_trigger => "http://lv2plug.in/ns/ext/port-props#trigger",
+    pprops_not_automatic => "http://lv2plug.in/ns/ext/port-props#notAutomatic",
+    pprops_range_steps => "http://lv2plug.in/ns/ext/port-props#rangeSteps",
+    pprops_display_priority => "http://lv2plug.in/ns/ext/port-props#displayPriority",
+    urid_map => "http://lv2plug.in/ns/ext/urid#map",
+    urid_unmap => "http://lv2plug.in/ns/ext/urid#unmap",
+    rdf_type => "http://www.w3.org/1999/02/22-rdf-syntax-ns#type",
+    rdfs_label => "http://www.w3.org/2000/01/rdf-schema#label",
+    rdfs_comment => "http://www.w3.org/2000/01/rdf-schema#comment",
+    rdfs_range => "http://www.w3.org/2000/01/rdf-schema#range",
+    rdfs_see_also => "http://www.w3.org/2000/01/rdf-schema#seeAlso",
+}
+
+impl Debug for Uris {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Uris").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::World;
+
+    #[test]
+    fn test_uris() {
+        let world = World::new();
+        let uris = world.uris();
+        assert_eq!(
+            uris.midi_midi_event.as_uri(),
+            Some("http://lv2plug.in/ns/ext/midi#MidiEvent")
+        );
+        assert_eq!(
+            uris.lv2_input_port,
+            world.new_uri("http://lv2plug.in/ns/lv2core#InputPort")
+        );
+        // Every handle to the world shares the same nodes.
+        let other = World::from_life(world.life.clone());
+        assert!(std::ptr::eq(other.uris(), uris));
+        drop(other);
+
+        let cloned = uris.clone();
+        drop(world);
+        assert_eq!(
+            cloned.time_position.as_uri(),
+            Some("http://lv2plug.in/ns/ext/time#Position")
+        );
+    }
+}
@@ -0,0 +1,585 @@
+use crate::instance::{ActiveInstance, Instance};
+use lv2_raw::{LV2Feature, LV2Handle};
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::{JoinHandle, Thread};
+
+/// The URI for the `work:schedule` feature.
+pub const WORKER_SCHEDULE_URI: &str = "http://lv2plug.in/ns/ext/worker#schedule";
+/// The URI for the `work:interface` extension data.
+pub const WORKER_INTERFACE_URI: &str = "http://lv2plug.in/ns/ext/worker#interface";
+
+const WORKER_SCHEDULE_URI_C: &[u8] = b"http://lv2plug.in/ns/ext/worker#schedule\0";
+
+/// Status code for worker functions.
+pub type LV2WorkerStatus = u32;
+/// Completed successfully.
+pub const LV2_WORKER_SUCCESS: LV2WorkerStatus = 0;
+/// Unknown error.
+pub const LV2_WORKER_ERR_UNKNOWN: LV2WorkerStatus = 1;
+/// Failed due to lack of space.
+pub const LV2_WORKER_ERR_NO_SPACE: LV2WorkerStatus = 2;
+
+/// Opaque handle for `LV2WorkerRespondFunction`.
+pub type LV2WorkerRespondHandle = *mut c_void;
+
+/// A function to respond to run() from the worker method.
+pub type LV2WorkerRespondFunction = extern "C" fn(
+    handle: LV2WorkerRespondHandle,
+    size: u32,
+    data: *const c_void,
+) -> LV2WorkerStatus;
+
+/// The `work:interface` extension data provided by plugins.
+#[repr(C)]
+pub struct LV2WorkerInterface {
+    /// The worker method. This is called by the host in a non-realtime
+    /// context as requested, possibly with an arbitrary message to handle.
+    pub work: Option<
+        unsafe extern "C" fn(
+            instance: LV2Handle,
+            respond: LV2WorkerRespondFunction,
+            handle: LV2WorkerRespondHandle,
+            size: u32,
+            data: *const c_void,
+        ) -> LV2WorkerStatus,
+    >,
+
+    /// Handle a response from the worker. This is called by the host in the
+    /// `run()` context when a response from the worker is ready.
+    pub work_response: Option<
+        unsafe extern "C" fn(
+            instance: LV2Handle,
+            size: u32,
+            body: *const c_void,
+        ) -> LV2WorkerStatus,
+    >,
+
+    /// Called when all responses for this cycle have been delivered.
+    pub end_run: Option<unsafe extern "C" fn(instance: LV2Handle) -> LV2WorkerStatus>,
+}
+
+/// Opaque handle for `LV2WorkerSchedule`.
+pub type LV2WorkerScheduleHandle = *mut c_void;
+
+/// The `work:schedule` feature provided by the host.
+#[repr(C)]
+pub struct LV2WorkerSchedule {
+    /// Opaque host data.
+    pub handle: LV2WorkerScheduleHandle,
+
+    /// Request from run() that the host call the worker.
+    pub schedule_work: extern "C" fn(
+        handle: LV2WorkerScheduleHandle,
+        size: u32,
+        data: *const c_void,
+    ) -> LV2WorkerStatus,
+}
+
+/// A host implementation of the LV2 worker extension.
+///
+/// The worker provides the `work:schedule` feature to plugins. Work that is
+/// scheduled by the plugin during `run()` is performed on a background thread,
+/// or immediately for synchronous workers, and the responses are delivered to
+/// the plugin by `Worker::end_run`.
+///
+/// Requests and responses are passed through preallocated lock free queues of
+/// `Worker::QUEUE_CAPACITY` bytes, so scheduling work and delivering the
+/// responses neither locks nor allocates on the thread that runs the plugin.
+/// Synchronous workers perform the work within `run()` and are not realtime
+/// safe.
+///
+/// # Example
+/// ```no_run
+/// use lilv::feature::FeatureSet;
+/// use lilv::worker::Worker;
+///
+/// let world = lilv::World::with_load_all();
+/// let plugin = world.plugins().iter().next().unwrap();
+/// let mut worker = Worker::new();
+/// let features = FeatureSet::new().with_worker(&worker);
+/// let instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+/// unsafe { worker.attach(&instance) };
+/// let mut instance = unsafe { instance.activate() };
+/// // Connect ports...
+/// unsafe { worker.run(&mut instance, 64) };
+/// ```
+pub struct Worker {
+    shared: Arc<WorkerShared>,
+    thread: Option<JoinHandle<()>>,
+    // Holds each response while it is delivered to the plugin.
+    response: Box<[u8]>,
+}
+
+struct WorkerShared {
+    schedule: LV2WorkerSchedule,
+    feature: LV2Feature,
+    synchronous: bool,
+    // The attached instance. The interface is stored last and is null until
+    // the worker is attached.
+    handle: AtomicPtr<c_void>,
+    interface: AtomicPtr<LV2WorkerInterface>,
+    requests: RingBuffer,
+    responses: RingBuffer,
+    thread: OnceLock<Thread>,
+    exit: AtomicBool,
+}
+
+// The raw pointers within `WorkerShared` point to data owned by `WorkerShared`
+// or to the attached plugin instance, which must outlive the worker.
+unsafe impl Send for WorkerShared {}
+unsafe impl Sync for WorkerShared {}
+
+impl Worker {
+    /// The number of bytes that are allocated for each of the request and
+    /// response queues. Each message takes 4 bytes more than its size.
+    pub const QUEUE_CAPACITY: usize = 8192;
+
+    /// Create a new worker that performs work on a background thread.
+    #[must_use]
+    pub fn new() -> Worker {
+        Worker::with_mode(false)
+    }
+
+    /// Create a new worker that performs work immediately when it is scheduled.
+    /// This is useful for offline rendering where blocking within `run()` is
+    /// acceptable.
+    #[must_use]
+    pub fn new_synchronous() -> Worker {
+        Worker::with_mode(true)
+    }
+
+    fn with_mode(synchronous: bool) -> Worker {
+        let mut shared = Arc::new(WorkerShared {
+            schedule: LV2WorkerSchedule {
+                handle: std::ptr::null_mut(),
+                schedule_work,
+            },
+            feature: LV2Feature {
+                uri: WORKER_SCHEDULE_URI_C.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            synchronous,
+            handle: AtomicPtr::new(std::ptr::null_mut()),
+            interface: AtomicPtr::new(std::ptr::null_mut()),
+            requests: RingBuffer::new(Worker::QUEUE_CAPACITY),
+            responses: RingBuffer::new(Worker::QUEUE_CAPACITY),
+            thread: OnceLock::new(),
+            exit: AtomicBool::new(false),
+        });
+        let handle = Arc::as_ptr(&shared) as *mut c_void;
+        // `shared` has not been shared yet so it is always unique.
+        if let Some(shared_mut) = Arc::get_mut(&mut shared) {
+            shared_mut.schedule.handle = handle;
+            shared_mut.feature.data = (&mut shared_mut.schedule as *mut LV2WorkerSchedule).cast();
+        }
+        let response = vec![0; shared.responses.capacity()].into_boxed_slice();
+        Worker {
+            shared,
+            thread: None,
+            response,
+        }
+    }
+
+    /// Returns `true` if work is performed immediately when it is scheduled.
+    #[must_use]
+    pub fn is_synchronous(&self) -> bool {
+        self.shared.synchronous
+    }
+
+    /// The `work:schedule` feature.
+    #[must_use]
+    pub fn feature(&self) -> &LV2Feature {
+        &self.shared.feature
+    }
+
+    /// The data that owns the `work:schedule` feature. This keeps the feature
+    /// valid after the worker itself has been dropped.
+    pub(crate) fn feature_owner(&self) -> Arc<dyn Any + Send + Sync> {
+        self.shared.clone()
+    }
+
+    /// Attach the worker to `instance`. Returns `false` if the instance does
+    /// not provide the `work:interface` extension data.
+    ///
+    /// # Safety
+    /// Getting the extension data calls the plugin's code, which itself may be
+    /// unsafe. The worker will call into `instance` until it is dropped, so
+    /// `instance` must outlive the worker.
+    pub unsafe fn attach(&mut self, instance: &Instance) -> bool {
+        let interface = match instance.extension_data::<LV2WorkerInterface>(WORKER_INTERFACE_URI) {
+            Some(interface) => interface,
+            None => return false,
+        };
+        self.shared
+            .handle
+            .store(instance.handle(), Ordering::Relaxed);
+        self.shared
+            .interface
+            .store(interface.as_ptr(), Ordering::Release);
+        if !self.shared.synchronous && self.thread.is_none() {
+            let shared = self.shared.clone();
+            let thread = std::thread::spawn(move || shared.work_loop());
+            let _ = self.shared.thread.set(thread.thread().clone());
+            self.thread = Some(thread);
+        }
+        true
+    }
+
+    /// Deliver all the responses from the worker to the plugin and notify it
+    /// that the run cycle has ended. This should be called after every call to
+    /// `ActiveInstance::run`.
+    pub fn end_run(&mut self) {
+        let (handle, interface) = match self.shared.attached() {
+            Some(attached) => attached,
+            None => return,
+        };
+        // The plugin may schedule more work while handling a response.
+        while let Some(size) = self.shared.responses.pop(&mut self.response) {
+            if let Some(work_response) = interface.work_response {
+                #[allow(clippy::cast_possible_truncation)]
+                unsafe {
+                    work_response(handle, size as u32, self.response.as_ptr().cast())
+                };
+            }
+        }
+        if let Some(end_run) = interface.end_run {
+            unsafe { end_run(handle) };
+        }
+    }
+
+    /// Run `instance` for `sample_count` frames and then deliver the worker
+    /// responses with `end_run`.
+    ///
+    /// # Safety
+    /// Calling external code may be unsafe.
+    pub unsafe fn run(&mut self, instance: &mut ActiveInstance, sample_count: usize) {
+        instance.run(sample_count);
+        self.end_run();
+    }
+}
+
+impl WorkerShared {
+    fn attached(&self) -> Option<(LV2Handle, &LV2WorkerInterface)> {
+        let interface = self.interface.load(Ordering::Acquire);
+        if interface.is_null() {
+            None
+        } else {
+            let handle = self.handle.load(Ordering::Relaxed);
+            Some((handle, unsafe { &*interface }))
+        }
+    }
+
+    fn work_loop(&self) {
+        let mut request = vec![0; self.requests.capacity()];
+        while !self.exit.load(Ordering::Acquire) {
+            match self.requests.pop(&mut request) {
+                Some(size) => {
+                    self.work(&request[..size]);
+                }
+                None => std::thread::park(),
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn work(&self, request: &[u8]) -> LV2WorkerStatus {
+        let (handle, interface) = match self.attached() {
+            Some(attached) => attached,
+            None => return LV2_WORKER_ERR_UNKNOWN,
+        };
+        let work = match interface.work {
+            Some(work) => work,
+            None => return LV2_WORKER_ERR_UNKNOWN,
+        };
+        unsafe {
+            work(
+                handle,
+                respond,
+                (self as *const WorkerShared as *mut WorkerShared).cast(),
+                request.len() as u32,
+                request.as_ptr().cast(),
+            )
+        }
+    }
+}
+
+impl Default for Worker {
+    fn default() -> Worker {
+        Worker::new()
+    }
+}
+
+impl Debug for Worker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Worker")
+            .field("synchronous", &self.shared.synchronous)
+            .field("attached", &self.shared.attached().is_some())
+            .finish()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.shared.exit.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+// A queue of messages with a single producer and a single consumer that
+// neither locks nor allocates. Each message is stored as its size followed by
+// its bytes.
+struct RingBuffer {
+    data: Box<[UnsafeCell<u8>]>,
+    // The total number of bytes that have been read and written. The capacity
+    // is a power of two so that the positions stay valid when they wrap.
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> RingBuffer {
+        let capacity = capacity.next_power_of_two();
+        RingBuffer {
+            data: (0..capacity).map(|_| UnsafeCell::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    // Copy `bytes` into the buffer starting at `position`. Only the producer
+    // may write to the free part of the buffer.
+    unsafe fn copy_in(&self, position: usize, bytes: &[u8]) {
+        let data = self.data.as_ptr() as *mut u8;
+        let offset = position % self.capacity();
+        let first = bytes.len().min(self.capacity() - offset);
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.add(offset), first);
+        std::ptr::copy_nonoverlapping(bytes[first..].as_ptr(), data, bytes.len() - first);
+    }
+
+    // Copy the bytes starting at `position` into `bytes`. Only the consumer
+    // may read from the used part of the buffer.
+    unsafe fn copy_out(&self, position: usize, bytes: &mut [u8]) {
+        let data = self.data.as_ptr() as *const u8;
+        let offset = position % self.capacity();
+        let first = bytes.len().min(self.capacity() - offset);
+        std::ptr::copy_nonoverlapping(data.add(offset), bytes.as_mut_ptr(), first);
+        std::ptr::copy_nonoverlapping(data, bytes[first..].as_mut_ptr(), bytes.len() - first);
+    }
+
+    // Add `message` to the queue. Returns `false` if there is not enough
+    // space. This must only be called by the producer.
+    fn push(&self, message: &[u8]) -> bool {
+        let size = match u32::try_from(message.len()) {
+            Ok(size) => size,
+            Err(_) => return false,
+        };
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        let len = 4 + message.len();
+        if len > self.capacity() - write.wrapping_sub(read) {
+            return false;
+        }
+        unsafe {
+            self.copy_in(write, &size.to_ne_bytes());
+            self.copy_in(write.wrapping_add(4), message);
+        }
+        self.write.store(write.wrapping_add(len), Ordering::Release);
+        true
+    }
+
+    // Move the next message into `buffer`, which must be at least as large as
+    // the capacity, and return its size. This must only be called by the
+    // consumer.
+    fn pop(&self, buffer: &mut [u8]) -> Option<usize> {
+        let read = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        if read == write {
+            return None;
+        }
+        let mut size = [0; 4];
+        unsafe { self.copy_out(read, &mut size) };
+        let size = u32::from_ne_bytes(size) as usize;
+        unsafe { self.copy_out(read.wrapping_add(4), &mut buffer[..size]) };
+        self.read
+            .store(read.wrapping_add(4 + size), Ordering::Release);
+        Some(size)
+    }
+}
+
+fn bytes<'a>(size: u32, data: *const c_void) -> &'a [u8] {
+    if data.is_null() || size == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data.cast(), size as usize) }
+    }
+}
+
+extern "C" fn schedule_work(
+    handle: LV2WorkerScheduleHandle,
+    size: u32,
+    data: *const c_void,
+) -> LV2WorkerStatus {
+    if handle.is_null() {
+        return LV2_WORKER_ERR_UNKNOWN;
+    }
+    let shared = unsafe { &*handle.cast::<WorkerShared>() };
+    let request = bytes(size, data);
+    if shared.synchronous {
+        shared.work(request)
+    } else if shared.requests.push(request) {
+        if let Some(thread) = shared.thread.get() {
+            thread.unpark();
+        }
+        LV2_WORKER_SUCCESS
+    } else {
+        LV2_WORKER_ERR_NO_SPACE
+    }
+}
+
+extern "C" fn respond(
+    handle: LV2WorkerRespondHandle,
+    size: u32,
+    data: *const c_void,
+) -> LV2WorkerStatus {
+    if handle.is_null() {
+        return LV2_WORKER_ERR_UNKNOWN;
+    }
+    let shared = unsafe { &*handle.cast::<WorkerShared>() };
+    if shared.responses.push(bytes(size, data)) {
+        LV2_WORKER_SUCCESS
+    } else {
+        LV2_WORKER_ERR_NO_SPACE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_without_instance() {
+        let mut worker = Worker::new_synchronous();
+        let feature = worker.feature();
+        let uri = unsafe { std::ffi::CStr::from_ptr(feature.uri) };
+        assert_eq!(uri.to_str(), Ok(WORKER_SCHEDULE_URI));
+
+        let schedule = unsafe { &*feature.data.cast::<LV2WorkerSchedule>() };
+        let data = [1u8, 2, 3];
+        let status = (schedule.schedule_work)(schedule.handle, 3, data.as_ptr().cast());
+        assert_eq!(status, LV2_WORKER_ERR_UNKNOWN);
+        worker.end_run();
+    }
+
+    #[test]
+    fn test_ring_buffer_wraps() {
+        let ring = RingBuffer::new(16);
+        let mut buffer = [0; 16];
+        for i in 0..10u8 {
+            assert!(ring.push(&[i, i + 1, i + 2]));
+            assert_eq!(ring.pop(&mut buffer), Some(3));
+            assert_eq!(&buffer[..3], &[i, i + 1, i + 2]);
+        }
+        assert!(ring.push(&[0; 12]));
+        assert!(!ring.push(&[0]));
+        assert_eq!(ring.pop(&mut buffer), Some(12));
+        assert_eq!(ring.pop(&mut buffer), None);
+    }
+
+    // Set the sample of eg-sampler and wait for the plugin to report that the
+    // worker loaded it.
+    fn load_sample(mut worker: Worker) {
+        use crate::atom::AtomValue;
+        use crate::buffer::PortBuffers;
+        use crate::feature::FeatureSet;
+        use crate::patch::Patch;
+        use crate::urid::UridMapper;
+
+        const SAMPLE: &str = "http://lv2plug.in/plugins/eg-sampler#sample";
+        let world = crate::World::with_load_all();
+        let plugin = world
+            .plugins()
+            .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-sampler"))
+            .unwrap();
+        let mapper = UridMapper::new();
+        let patch = Patch::new(&mapper);
+        let features = crate::render::host_features(
+            FeatureSet::new()
+                .with_worker(&worker)
+                .with_flag("http://lv2plug.in/ns/ext/state#loadDefaultState"),
+            &mapper,
+            44100.0,
+            64,
+        );
+        let mut instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+        let mut buffers = PortBuffers::new(&plugin, &mapper, 64);
+        unsafe {
+            buffers.connect(&mut instance);
+            assert!(worker.attach(&instance));
+        }
+        let mut instance = unsafe { instance.activate() };
+        let symbol = |port: Option<crate::port::Port>| {
+            port.and_then(|p| p.symbol()?.as_str().map(str::to_string))
+                .unwrap()
+        };
+        let input = symbol(plugin.patch_input_port());
+        let output = symbol(plugin.patch_output_port());
+
+        // Use a copy of the bundled sample so that the notification can not
+        // be confused with the default sample.
+        let bundle = plugin.bundle_uri().path().unwrap().1;
+        let sample = std::env::temp_dir().join(format!(
+            "lilv-test-worker-{}-{}.wav",
+            std::process::id(),
+            worker.is_synchronous()
+        ));
+        std::fs::copy(std::path::Path::new(&bundle).join("click.wav"), &sample).unwrap();
+        let sample_str = sample.to_str().unwrap();
+
+        let mut loaded = false;
+        for block in 0..1000 {
+            buffers.reset_atom_sequences();
+            if block == 0 {
+                let mut writer = buffers.sequence_writer(&input).unwrap();
+                assert!(patch.write_set(&mut writer, 0, SAMPLE, &AtomValue::Path(sample_str)));
+            }
+            unsafe { worker.run(&mut instance, 64) };
+            let events = buffers.sequence(&output).unwrap();
+            loaded = patch.read_sets(events).any(|set| {
+                set.property == SAMPLE
+                    && set.value.value(patch.atom_urids()) == AtomValue::Path(sample_str)
+            });
+            if loaded {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        // The instance must outlive the worker.
+        drop(worker);
+        drop(unsafe { instance.deactivate() });
+        std::fs::remove_file(&sample).unwrap();
+        assert!(loaded);
+    }
+
+    #[test]
+    fn test_sampler_threaded() {
+        load_sample(Worker::new());
+    }
+
+    #[test]
+    fn test_sampler_synchronous() {
+        load_sample(Worker::new_synchronous());
+    }
+}
@@ -1,10 +1,15 @@
+use crate::error::{c_string, Error};
 use crate::node::{Node, Nodes};
 use crate::plugin::Class;
 use crate::plugin::Plugins;
+use crate::uris::{UriTable, Uris};
 use lilv_sys as lib;
 use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock, Weak};
+
+const OPTION_LV2_PATH: &str = "http://drobilla.net/ns/lilv#lv2-path";
 
 unsafe impl Send for Life {}
 unsafe impl Sync for Life {}
@@ -13,12 +18,27 @@ unsafe impl Sync for Life {}
 /// data (plugins, UIs, and extensions).
 pub struct World {
     pub(crate) life: Arc<Life>,
+    uris: OnceLock<Arc<Uris>>,
 }
 
 #[doc(hidden)]
 #[allow(clippy::non_send_fields_in_send_ty)]
 pub struct Life {
     pub(crate) inner: Mutex<NonNull<lib::LilvWorldImpl>>,
+    uri_table: OnceLock<UriTable>,
+    // The nodes of `Uris` keep `Life` alive, so only a weak reference is kept
+    // here to share them between the handles of the world.
+    uris: Mutex<Weak<Uris>>,
+    lv2_path: Mutex<Option<Vec<PathBuf>>>,
+}
+
+impl Life {
+    /// The nodes for well known URIs, created on first use. This must not be
+    /// called while `inner` is locked.
+    pub(crate) fn uri_table(&self) -> &UriTable {
+        self.uri_table
+            .get_or_init(|| UriTable::new(*self.inner.lock()))
+    }
 }
 
 impl World {
@@ -31,7 +51,19 @@ impl World {
         Self {
             life: Arc::new(Life {
                 inner: Mutex::new(NonNull::new(unsafe { lib::lilv_world_new() }).unwrap()),
+                uri_table: OnceLock::new(),
+                uris: Mutex::new(Weak::new()),
+                lv2_path: Mutex::new(None),
             }),
+            uris: OnceLock::new(),
+        }
+    }
+
+    /// Create a world that shares `life` with another world.
+    pub(crate) fn from_life(life: Arc<Life>) -> World {
+        World {
+            life,
+            uris: OnceLock::new(),
         }
     }
 
@@ -48,9 +80,36 @@ impl World {
         world.load_all();
         world
     }
+
+    /// Loads a new world with only the test bundle in `tests/bundles`. The
+    /// build script copies the bundle and builds its plugin in `OUT_DIR`.
+    #[cfg(test)]
+    pub(crate) fn with_test_bundle() -> World {
+        let world = World::new();
+        let bundle = Path::new(env!("OUT_DIR")).join("lilv-test.lv2");
+        world.load_bundle_path(&bundle).unwrap();
+        world
+    }
 }
 
 impl World {
+    /// Get the nodes for well known URIs. The nodes are created on the first
+    /// call and shared with the other handles of the world that use them.
+    #[must_use]
+    pub fn uris(&self) -> &Uris {
+        self.uris.get_or_init(|| {
+            let mut shared = self.life.uris.lock();
+            match shared.upgrade() {
+                Some(uris) => uris,
+                None => {
+                    let uris = Arc::new(Uris::new(&self.life));
+                    *shared = Arc::downgrade(&uris);
+                    uris
+                }
+            }
+        })
+    }
+
     /// Get the parent of all other plugin classes, lv2:Plugin.
     #[must_use]
     pub fn plugin_class(&self) -> Option<Class> {
@@ -84,14 +143,85 @@ impl World {
     /// # Panics
     /// Panics if uri could not be converted to a `CString`.
     pub fn set_option(&self, uri: &str, value: &Node) {
+        self.try_set_option(uri, value).unwrap();
+    }
+
+    /// Sets an option for the world.
+    ///
+    /// # Errors
+    /// Returns an error if `uri` contains a null byte.
+    pub fn try_set_option(&self, uri: &str, value: &Node) -> Result<(), Error> {
+        let uri = c_string(uri)?;
         let world = self.life.inner.lock();
-        let uri = std::ffi::CString::new(uri).unwrap();
         let value = value.inner.as_ptr();
 
-        unsafe { lib::lilv_world_set_option(world.as_ptr(), uri.as_ptr().cast(), value) }
+        unsafe { lib::lilv_world_set_option(world.as_ptr(), uri.as_ptr().cast(), value) };
+        Ok(())
+    }
+}
+
+impl World {
+    /// Set the directories that are searched for bundles, instead of the
+    /// `LV2_PATH` environment variable.
+    ///
+    /// Older versions of Lilv ignore this in `load_all`. Use
+    /// [`load_all_bundles`](#method.load_all_bundles) to always search `paths`.
+    ///
+    /// # Errors
+    /// Returns `Error::InvalidString` if a path contains the path separator or
+    /// a null byte.
+    pub fn set_lv2_path(&self, paths: &[PathBuf]) -> Result<(), Error> {
+        let joined = std::env::join_paths(paths)
+            .map_err(|e| Error::InvalidString(e.to_string()))?
+            .to_string_lossy()
+            .into_owned();
+        let value = self.try_new_string(&joined)?;
+        self.try_set_option(OPTION_LV2_PATH, &value)?;
+        *self.life.lv2_path.lock() = Some(paths.to_vec());
+        Ok(())
+    }
+
+    /// The directories that are searched for bundles. This is the path set by
+    /// [`set_lv2_path`](#method.set_lv2_path), the `LV2_PATH` environment
+    /// variable, or the default path of the system, in that order.
+    ///
+    /// The default path is an approximation of the one that Lilv was compiled
+    /// with. A Lilv that was built with a different default may search other
+    /// directories in `load_all`.
+    #[must_use]
+    pub fn lv2_path(&self) -> Vec<PathBuf> {
+        if let Some(paths) = self.life.lv2_path.lock().as_ref() {
+            return paths.clone();
+        }
+        match std::env::var_os("LV2_PATH") {
+            Some(paths) => std::env::split_paths(&paths).collect(),
+            None => default_lv2_path(),
+        }
     }
 }
 
+// Mirrors the default `LV2_PATH` that Lilv is usually compiled with. The real
+// default is fixed when Lilv is built and is not exposed by its API.
+fn default_lv2_path() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut paths = Vec::new();
+    if cfg!(target_os = "macos") {
+        paths.extend(home.as_ref().map(|h| h.join("Library/Audio/Plug-Ins/LV2")));
+        paths.extend(home.as_ref().map(|h| h.join(".lv2")));
+        paths.push(PathBuf::from("/usr/local/lib/lv2"));
+        paths.push(PathBuf::from("/usr/lib/lv2"));
+        paths.push(PathBuf::from("/Library/Audio/Plug-Ins/LV2"));
+    } else if cfg!(windows) {
+        paths.extend(std::env::var_os("APPDATA").map(|p| PathBuf::from(p).join("LV2")));
+        paths.extend(std::env::var_os("COMMONPROGRAMFILES").map(|p| PathBuf::from(p).join("LV2")));
+    } else {
+        paths.extend(home.as_ref().map(|h| h.join(".lv2")));
+        paths.push(PathBuf::from("/usr/local/lib/lv2"));
+        paths.push(PathBuf::from("/usr/lib/lv2"));
+    }
+    paths
+}
+
 impl World {
     /// Creates a new URI value.
     ///
@@ -99,47 +229,64 @@ impl World {
     /// Panics on failure.
     #[must_use]
     pub fn new_uri(&self, uri: &str) -> Node {
+        self.try_new_uri(uri).unwrap()
+    }
+
+    /// Creates a new URI value.
+    ///
+    /// # Errors
+    /// Returns an error if `uri` contains a null byte or is not a valid URI.
+    pub fn try_new_uri(&self, uri: &str) -> Result<Node, Error> {
+        let uri_c = c_string(uri)?;
         let world = self.life.inner.lock();
-        let uri = std::ffi::CString::new(uri).unwrap();
 
         {
             let ptr =
-                NonNull::new(unsafe { lib::lilv_new_uri(world.as_ptr(), uri.as_ptr().cast()) })
-                    .unwrap();
+                NonNull::new(unsafe { lib::lilv_new_uri(world.as_ptr(), uri_c.as_ptr().cast()) })
+                    .ok_or_else(|| Error::InvalidUri(uri.to_string()))?;
             let world = self.life.clone();
-            Node {
+            Ok(Node {
                 inner: ptr,
                 borrowed: false,
                 life: world,
-            }
+            })
         }
     }
 
-    /// Creates a new file URI value.
+    /// Creates a new file URI value. A `host` that contains a null byte is
+    /// ignored.
     ///
     /// # Panics
     /// Panics on failure.
     #[must_use]
     pub fn new_file_uri(&self, host: Option<&str>, path: &str) -> Node {
+        let host = host.filter(|h| !h.contains('\0'));
+        self.try_new_file_uri(host, path).unwrap()
+    }
+
+    /// Creates a new file URI value.
+    ///
+    /// # Errors
+    /// Returns an error if `host` or `path` contain a null byte or do not form
+    /// a valid URI.
+    pub fn try_new_file_uri(&self, host: Option<&str>, path: &str) -> Result<Node, Error> {
+        let host = host.map(c_string).transpose()?;
+        let path_c = c_string(path)?;
         let world = self.life.inner.lock();
-        let host = host
-            .iter()
-            .find_map(|h| std::ffi::CString::new(h.as_bytes()).ok());
-        let path = std::ffi::CString::new(path.as_bytes()).unwrap();
 
-        let host_ptr = host.map_or(std::ptr::null(), |h| h.as_ptr());
-        let path_ptr = path.as_ptr();
+        let host_ptr = host.as_ref().map_or(std::ptr::null(), |h| h.as_ptr());
+        let path_ptr = path_c.as_ptr();
 
         {
             let ptr =
                 NonNull::new(unsafe { lib::lilv_new_file_uri(world.as_ptr(), host_ptr, path_ptr) })
-                    .unwrap();
+                    .ok_or_else(|| Error::InvalidUri(path.to_string()))?;
             let world = self.life.clone();
-            Node {
+            Ok(Node {
                 inner: ptr,
                 borrowed: false,
                 life: world,
-            }
+            })
         }
     }
 
@@ -149,19 +296,27 @@ impl World {
     /// Panics on failure.
     #[must_use]
     pub fn new_string(&self, string: &str) -> Node {
+        self.try_new_string(string).unwrap()
+    }
+
+    /// Creates a new string value (with no language).
+    ///
+    /// # Errors
+    /// Returns an error if `string` contains a null byte.
+    pub fn try_new_string(&self, string: &str) -> Result<Node, Error> {
+        let string_c = c_string(string)?;
         let world = self.life.inner.lock();
-        let string = std::ffi::CString::new(string).unwrap();
 
         {
             let ptr =
-                NonNull::new(unsafe { lib::lilv_new_string(world.as_ptr(), string.as_ptr()) })
-                    .unwrap();
+                NonNull::new(unsafe { lib::lilv_new_string(world.as_ptr(), string_c.as_ptr()) })
+                    .ok_or_else(|| Error::InvalidString(string.to_string()))?;
             let world = self.life.clone();
-            Node {
+            Ok(Node {
                 inner: ptr,
                 borrowed: false,
                 life: world,
-            }
+            })
         }
     }
 
@@ -242,6 +397,70 @@ impl World {
         unsafe { lib::lilv_world_load_bundle(world.as_ptr(), bundle_uri) }
     }
 
+    /// Loads the bundle in the directory `path`, for example
+    /// `/usr/lib/lv2/foo.lv2`. Returns the URI of the bundle.
+    ///
+    /// # Errors
+    /// Returns `Error::BundleNotFound` if `path` is not a bundle directory and
+    /// `Error::InvalidString` if the path can not be converted to a URI.
+    pub fn load_bundle_path(&self, path: &Path) -> Result<Node, Error> {
+        if !path.join("manifest.ttl").is_file() {
+            return Err(Error::BundleNotFound(path.display().to_string()));
+        }
+        let path = path
+            .canonicalize()
+            .map_err(|_| Error::BundleNotFound(path.display().to_string()))?;
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidString(path.display().to_string()))?;
+        let uri = self.try_new_file_uri(None, &format!("{}/", path.trim_end_matches('/')))?;
+        self.load_bundle(&uri);
+        Ok(uri)
+    }
+
+    /// Loads all the bundles within the directories of
+    /// [`lv2_path`](#method.lv2_path), then the specifications and plugin
+    /// classes. Returns the URIs of the bundles that were loaded.
+    ///
+    /// Without `LV2_PATH` or [`set_lv2_path`](#method.set_lv2_path), the
+    /// directories are only an approximation of the default path of Lilv. Use
+    /// [`load_all_in`](#method.load_all_in) to search an exact path.
+    pub fn load_all_bundles(&self) -> Vec<Node> {
+        self.load_all_in(&self.lv2_path())
+    }
+
+    /// Loads all the bundles within `dirs`, then the specifications and plugin
+    /// classes. Returns the URIs of the bundles that were loaded. Unlike
+    /// `load_all`, the `LV2_PATH` environment variable is not used.
+    ///
+    /// # Example
+    /// ```
+    /// let world = lilv::World::new();
+    /// let bundles = world.load_all_in(&[std::path::PathBuf::from("/usr/lib/lv2")]);
+    /// println!("Loaded {} bundles", bundles.len());
+    /// ```
+    pub fn load_all_in(&self, dirs: &[PathBuf]) -> Vec<Node> {
+        let mut bundles = Vec::new();
+        for dir in dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut paths: Vec<PathBuf> = entries.filter_map(|e| Some(e.ok()?.path())).collect();
+            paths.sort();
+            for path in paths {
+                if let Ok(uri) = self.load_bundle_path(&path) {
+                    if !bundles.contains(&uri) {
+                        bundles.push(uri);
+                    }
+                }
+            }
+        }
+        self.load_specifications();
+        self.load_plugin_classes();
+        bundles
+    }
+
     /// Loads all specifications from currently loaded bundles.
     ///
     /// This is for hosts that explicitly load specific bundles, its use is not
@@ -280,6 +499,22 @@ impl World {
         lib::lilv_world_unload_bundle(world.as_ptr(), bundle_uri) == 0
     }
 
+    /// Unload a specific bundle. See [`unload_bundle`](#method.unload_bundle).
+    ///
+    /// # Errors
+    /// Returns `Error::BundleNotFound` if the bundle is not loaded.
+    ///
+    /// # Safety
+    /// Unloading bundles that are in use by the host will cause undefined
+    /// behaviour.
+    pub unsafe fn try_unload_bundle(&self, bundle_uri: &Node) -> Result<(), Error> {
+        if self.unload_bundle(bundle_uri) {
+            Ok(())
+        } else {
+            Err(Error::BundleNotFound(bundle_uri.turtle_token()))
+        }
+    }
+
     /// Load all the data associated with the given resource.
     ///
     /// # Return
@@ -296,6 +531,18 @@ impl World {
         }
     }
 
+    /// Load all the data associated with the given resource.
+    ///
+    /// # Return
+    /// The number of files parsed.
+    ///
+    /// # Errors
+    /// Returns `Error::ParseFailure` if the data could not be loaded.
+    pub fn try_load_resource(&self, resource: &Node) -> Result<usize, Error> {
+        self.load_resource(resource)
+            .ok_or_else(|| Error::ParseFailure(resource.turtle_token()))
+    }
+
     /// Unload all the data associated with the given resource.
     ///
     /// # Safety
@@ -411,6 +658,9 @@ impl Default for World {
 impl Drop for Life {
     fn drop(&mut self) {
         unsafe {
+            if let Some(uris) = self.uri_table.get() {
+                uris.free();
+            }
             let world = self.inner.lock();
             lib::lilv_world_free(world.as_ptr());
         }
@@ -427,6 +677,39 @@ mod tests {
         w.load_all();
     }
 
+    #[test]
+    fn test_load_bundle_path() {
+        let w = World::new();
+        let dir = std::env::temp_dir().join(format!("lilv-test-empty-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(
+            w.load_bundle_path(&dir),
+            Err(Error::BundleNotFound(dir.display().to_string()))
+        );
+        assert!(w.load_all_in(std::slice::from_ref(&dir)).is_empty());
+        w.set_lv2_path(&[PathBuf::from("/a"), PathBuf::from("/b")])
+            .unwrap();
+        assert_eq!(w.lv2_path(), vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_bundle_path_amp() {
+        let uri = "http://lv2plug.in/plugins/eg-amp";
+        let dir = {
+            let installed = World::with_load_all();
+            let plugin = installed.plugins().plugin(&installed.new_uri(uri)).unwrap();
+            PathBuf::from(plugin.bundle_uri().path().unwrap().1)
+        };
+
+        let w = World::new();
+        assert!(w.plugins().plugin(&w.new_uri(uri)).is_none());
+        let bundle = w.load_bundle_path(&dir).unwrap();
+        assert!(bundle.is_uri());
+        let plugin = w.plugins().plugin(&w.new_uri(uri)).unwrap();
+        assert_eq!(plugin.bundle_uri(), bundle);
+    }
+
     #[test]
     fn test_new_node() {
         let w = World::new();
@@ -437,4 +720,16 @@ mod tests {
         assert!(w.new_file_uri(Some("me"), "/some/path").is_uri());
         assert!(w.new_string("string").is_string());
     }
+
+    #[test]
+    fn test_try_new_node() {
+        let w = World::new();
+        assert!(w.try_new_uri("http://lv2plug.in/ns/lv2core#Plugin").is_ok());
+        assert_eq!(
+            w.try_new_uri("http://bad\0uri").unwrap_err(),
+            Error::InvalidString("http://bad\0uri".to_string())
+        );
+        assert!(w.try_new_string("a\0b").is_err());
+        assert!(w.try_new_file_uri(None, "/a\0b").is_err());
+    }
 }
@@ -0,0 +1,228 @@
+// A plugin for the tests of the crate. It delays its stereo input by a fixed
+// latency that it reports on its latency port and provides `opts:interface`
+// for the nominal block length.
+//
+// The LV2 types are declared here so that building the plugin does not need
+// the LV2 headers.
+
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define PLUGIN_URI "http://example.org/lilv-test"
+#define LATENCY 4
+
+#define URID_MAP_URI "http://lv2plug.in/ns/ext/urid#map"
+#define OPTIONS_URI "http://lv2plug.in/ns/ext/options#options"
+#define OPTIONS_INTERFACE_URI "http://lv2plug.in/ns/ext/options#interface"
+#define ATOM_INT_URI "http://lv2plug.in/ns/ext/atom#Int"
+#define NOMINAL_BLOCK_LENGTH_URI "http://lv2plug.in/ns/ext/buf-size#nominalBlockLength"
+
+#define OPTIONS_INSTANCE 0
+#define OPTIONS_ERR_BAD_KEY (1 << 2)
+#define OPTIONS_ERR_BAD_VALUE (1 << 3)
+
+typedef void* LV2_Handle;
+typedef uint32_t LV2_URID;
+
+typedef struct {
+	const char* URI;
+	void* data;
+} LV2_Feature;
+
+typedef struct LV2_Descriptor {
+	const char* URI;
+	LV2_Handle (*instantiate)(const struct LV2_Descriptor* descriptor,
+	                          double sample_rate,
+	                          const char* bundle_path,
+	                          const LV2_Feature* const* features);
+	void (*connect_port)(LV2_Handle instance, uint32_t port, void* data);
+	void (*activate)(LV2_Handle instance);
+	void (*run)(LV2_Handle instance, uint32_t sample_count);
+	void (*deactivate)(LV2_Handle instance);
+	void (*cleanup)(LV2_Handle instance);
+	const void* (*extension_data)(const char* uri);
+} LV2_Descriptor;
+
+typedef struct {
+	void* handle;
+	LV2_URID (*map)(void* handle, const char* uri);
+} LV2_URID_Map;
+
+typedef struct {
+	uint32_t context;
+	uint32_t subject;
+	LV2_URID key;
+	uint32_t size;
+	LV2_URID type;
+	const void* value;
+} LV2_Options_Option;
+
+typedef struct {
+	uint32_t (*get)(LV2_Handle instance, LV2_Options_Option* options);
+	uint32_t (*set)(LV2_Handle instance, const LV2_Options_Option* options);
+} LV2_Options_Interface;
+
+enum { IN_LEFT, IN_RIGHT, OUT_LEFT, OUT_RIGHT, LEVEL, LATENCY_PORT };
+
+typedef struct {
+	const float* input[2];
+	float* output[2];
+	const float* level;
+	float* latency;
+	float delay[2][LATENCY];
+	uint32_t position;
+	LV2_URID atom_int;
+	LV2_URID nominal_block_length;
+	int32_t block_length;
+} TestPlugin;
+
+static uint32_t
+set_options(LV2_Handle instance, const LV2_Options_Option* options)
+{
+	TestPlugin* self = (TestPlugin*)instance;
+	uint32_t status = 0;
+	for (const LV2_Options_Option* o = options; o->key; ++o) {
+		if (!self->nominal_block_length || o->key != self->nominal_block_length) {
+			status |= OPTIONS_ERR_BAD_KEY;
+		} else if (o->type != self->atom_int || o->size != sizeof(int32_t)) {
+			status |= OPTIONS_ERR_BAD_VALUE;
+		} else {
+			self->block_length = *(const int32_t*)o->value;
+		}
+	}
+	return status;
+}
+
+static uint32_t
+get_options(LV2_Handle instance, LV2_Options_Option* options)
+{
+	TestPlugin* self = (TestPlugin*)instance;
+	uint32_t status = 0;
+	for (LV2_Options_Option* o = options; o->key; ++o) {
+		if (o->context != OPTIONS_INSTANCE || !self->nominal_block_length ||
+		    o->key != self->nominal_block_length) {
+			status |= OPTIONS_ERR_BAD_KEY;
+		} else {
+			o->size = sizeof(int32_t);
+			o->type = self->atom_int;
+			o->value = &self->block_length;
+		}
+	}
+	return status;
+}
+
+static LV2_Handle
+instantiate(const LV2_Descriptor* descriptor,
+            double sample_rate,
+            const char* bundle_path,
+            const LV2_Feature* const* features)
+{
+	(void)descriptor;
+	(void)sample_rate;
+	(void)bundle_path;
+
+	TestPlugin* self = (TestPlugin*)calloc(1, sizeof(TestPlugin));
+	if (!self) {
+		return NULL;
+	}
+	const LV2_Options_Option* options = NULL;
+	for (int i = 0; features && features[i]; ++i) {
+		if (!strcmp(features[i]->URI, URID_MAP_URI)) {
+			const LV2_URID_Map* map = (const LV2_URID_Map*)features[i]->data;
+			self->atom_int = map->map(map->handle, ATOM_INT_URI);
+			self->nominal_block_length = map->map(map->handle, NOMINAL_BLOCK_LENGTH_URI);
+		} else if (!strcmp(features[i]->URI, OPTIONS_URI)) {
+			options = (const LV2_Options_Option*)features[i]->data;
+		}
+	}
+	if (options) {
+		// Only the nominal block length is kept, the other options are ignored.
+		set_options(self, options);
+	}
+	return (LV2_Handle)self;
+}
+
+static void
+connect_port(LV2_Handle instance, uint32_t port, void* data)
+{
+	TestPlugin* self = (TestPlugin*)instance;
+	switch (port) {
+	case IN_LEFT:
+	case IN_RIGHT:
+		self->input[port - IN_LEFT] = (const float*)data;
+		break;
+	case OUT_LEFT:
+	case OUT_RIGHT:
+		self->output[port - OUT_LEFT] = (float*)data;
+		break;
+	case LEVEL:
+		self->level = (const float*)data;
+		break;
+	case LATENCY_PORT:
+		self->latency = (float*)data;
+		break;
+	}
+}
+
+static void
+activate(LV2_Handle instance)
+{
+	TestPlugin* self = (TestPlugin*)instance;
+	memset(self->delay, 0, sizeof(self->delay));
+	self->position = 0;
+}
+
+static void
+run(LV2_Handle instance, uint32_t sample_count)
+{
+	TestPlugin* self = (TestPlugin*)instance;
+	for (uint32_t i = 0; i < sample_count; ++i) {
+		for (int c = 0; c < 2; ++c) {
+			// Read the input first, the buffers may be shared.
+			const float in = self->input[c] ? self->input[c][i] : 0.0f;
+			const float out = self->delay[c][self->position];
+			self->delay[c][self->position] = in;
+			if (self->output[c]) {
+				self->output[c][i] = out;
+			}
+		}
+		self->position = (self->position + 1) % LATENCY;
+	}
+	if (self->latency) {
+		*self->latency = (float)LATENCY;
+	}
+}
+
+static void
+cleanup(LV2_Handle instance)
+{
+	free(instance);
+}
+
+static const void*
+extension_data(const char* uri)
+{
+	static const LV2_Options_Interface options = {get_options, set_options};
+	if (!strcmp(uri, OPTIONS_INTERFACE_URI)) {
+		return &options;
+	}
+	return NULL;
+}
+
+static const LV2_Descriptor descriptor = {
+	PLUGIN_URI,
+	instantiate,
+	connect_port,
+	activate,
+	run,
+	NULL,
+	cleanup,
+	extension_data,
+};
+
+__attribute__((visibility("default"))) const LV2_Descriptor*
+lv2_descriptor(uint32_t index)
+{
+	return index == 0 ? &descriptor : NULL;
+}
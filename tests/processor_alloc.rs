@@ -0,0 +1,154 @@
+use lilv::atom::{AtomUrids, AtomValue};
+use lilv::buffer::PortBuffers;
+use lilv::feature::FeatureSet;
+use lilv::urid::UridMapper;
+use lilv::worker::Worker;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// Counts the allocations made by threads that enabled counting.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn count() {
+    if COUNTING.with(Cell::get) {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count();
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Returns the number of allocations made by `f` on the current thread.
+fn allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    COUNTING.with(|c| c.set(true));
+    f();
+    COUNTING.with(|c| c.set(false));
+    ALLOCATIONS.load(Ordering::SeqCst) - before
+}
+
+#[test]
+fn test_process_does_not_allocate() {
+    let world = lilv::World::with_load_all();
+    let plugin = world
+        .plugins()
+        .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-amp"))
+        .unwrap();
+    let mapper = UridMapper::new();
+    let instance =
+        unsafe { plugin.instantiate_with_features(44100.0, &mapper.features()) }.unwrap();
+    let buffers = PortBuffers::new(&plugin, &mapper, 256);
+    let mut processor = unsafe { instance.activate().into_processor(buffers) };
+
+    let count = allocations(|| {
+        for block in 0..100 {
+            let buffers = processor.buffers_mut();
+            buffers.set_control("gain", -6.0);
+            if let Some(samples) = buffers.by_symbol_mut("in").and_then(|b| b.samples_mut()) {
+                samples.iter_mut().for_each(|s| *s = block as f32);
+            }
+            unsafe { processor.process(256) };
+            assert!(processor.latency().is_none());
+        }
+    });
+    assert_eq!(count, 0);
+
+    let (instance, _buffers) = processor.into_inner();
+    drop(unsafe { instance.deactivate() });
+}
+
+#[test]
+fn test_process_atom_ports_does_not_allocate() {
+    const SAMPLE: &str = "http://lv2plug.in/plugins/eg-sampler#sample";
+    let world = lilv::World::with_load_all();
+    let plugin = world
+        .plugins()
+        .plugin(&world.new_uri("http://lv2plug.in/plugins/eg-sampler"))
+        .unwrap();
+    let mapper = UridMapper::new();
+    let mut worker = Worker::new();
+    let features = FeatureSet::new()
+        .with_urid_mapper(&mapper)
+        .with_worker(&worker)
+        .with_flag("http://lv2plug.in/ns/ext/state#loadDefaultState");
+    let instance = unsafe { plugin.try_instantiate(44100.0, &features) }.unwrap();
+    assert!(unsafe { worker.attach(&instance) });
+    let buffers = PortBuffers::new(&plugin, &mapper, 256);
+    let mut processor = unsafe {
+        instance
+            .activate()
+            .into_processor_with_worker(buffers, worker)
+    };
+
+    // Mapping allocates, so everything is mapped before counting.
+    let urids = AtomUrids::new(&mapper);
+    let patch = |name: &str| mapper.map(&format!("http://lv2plug.in/ns/ext/patch#{}", name));
+    let (set, property, value) = (patch("Set"), patch("property"), patch("value"));
+    let sample = mapper.map(SAMPLE);
+    let bundle = plugin.bundle_uri().path().unwrap().1;
+    let path = Path::new(&bundle).join("click.wav");
+    let path = path.to_str().unwrap();
+
+    // Setting the sample schedules work for the worker thread, whose response
+    // is delivered by `process` and then reported on the notify port.
+    let mut loaded = false;
+    let count = allocations(|| {
+        for block in 0..1000 {
+            if block == 0 {
+                let buffers = processor.buffers_mut();
+                let mut writer = buffers.sequence_writer("control").unwrap();
+                let properties = [
+                    (property, AtomValue::Urid(sample)),
+                    (value, AtomValue::Path(path)),
+                ];
+                assert!(writer.write_object(0, 0, set, &properties));
+            }
+            unsafe { processor.process(256) };
+            let notify = processor.buffers().sequence("notify").unwrap();
+            loaded = notify.filter_map(|e| e.atom.object(&urids)).any(|object| {
+                object.otype == set
+                    && object.get(value).map(|v| v.value(&urids)) == Some(AtomValue::Path(path))
+            });
+            if loaded {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+    assert!(loaded);
+    assert_eq!(count, 0);
+
+    let (instance, _buffers) = processor.into_inner();
+    drop(unsafe { instance.deactivate() });
+}